@@ -0,0 +1,52 @@
+//! Compares deserializing a large JSON document through a [BufReader], a [Cursor] and a [SliceRead]
+//!
+//! Run with `cargo bench -p lib-contra`.
+use std::{
+    collections::HashMap,
+    env, fs,
+    fs::File,
+    hint::black_box,
+    io::{BufReader, Cursor},
+    time::Instant,
+};
+
+use lib_contra::deserialize::{json::JsonDeserializer, Deserialize, Peek, SliceRead};
+
+type Level = Vec<HashMap<String, Vec<i64>>>;
+
+fn level(entities: usize) -> String {
+    let entities: Vec<String> = (0..entities)
+        .map(|i| {
+            format!(
+                "\t{{\n\t\t\"position\": [\"{}\", \"-{}\", \"42\"],\n\t\t\"stats\": [0, 1, 2, 3, 4, 5, 6, 7]\n\t}}",
+                i, i
+            )
+        })
+        .collect();
+    format!("[\n{}\n]", entities.join(",\n"))
+}
+
+fn measure<'de, P: Peek<'de>>(name: &str, runs: u32, make: impl Fn() -> P) {
+    let start = Instant::now();
+    for _ in 0..runs {
+        let mut de = JsonDeserializer::new(make());
+        black_box(Level::deserialize(&mut de).expect("failed to deserialize level"));
+    }
+    println!("{:<8} {:>10.2?} per run", name, start.elapsed() / runs);
+}
+
+fn main() {
+    let json = level(20_000);
+    println!("document size: {} KiB", json.len() / 1024);
+
+    let path = env::temp_dir().join("contra_bench_level.json");
+    fs::write(&path, &json).expect("failed to write level");
+
+    measure("file", 1, || {
+        BufReader::new(File::open(&path).expect("failed to open level"))
+    });
+    measure("cursor", 3, || Cursor::new(json.as_bytes()));
+    measure("slice", 3, || SliceRead::new(json.as_bytes()));
+
+    fs::remove_file(&path).expect("failed to remove level");
+}
@@ -1,43 +1,51 @@
+pub mod args;
+pub mod binary;
+pub mod env;
 pub mod json;
 
 use std::{
+    borrow::Cow,
     collections::HashMap,
     hash::Hash,
-    io::{self, ErrorKind},
+    io::{self, BufRead, ErrorKind},
     marker::PhantomData,
+    ops::Deref,
 };
 
-use crate::error::AnyError;
+use crate::error::Error;
 
 use self::error::visiting_but_expected;
 
 macro_rules! decl_deserialize_primitive {
     ($deserialize_fn: ident) => {
-        fn $deserialize_fn<V: Visitor>(self, v: V) -> Result<V::Value, AnyError>;
+        fn $deserialize_fn<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error>;
     };
 }
 
 macro_rules! decl_visit_primitive {
     ($ttype: ident, $visit_fn: ident) => {
-        fn $visit_fn(self, _v: $ttype) -> Result<Self::Value, AnyError> {
-            Err(visiting_but_expected(stringify!($ttype), &self.expected_a()).into())
+        fn $visit_fn(self, _v: $ttype) -> Result<Self::Value, Error> {
+            Err(visiting_but_expected(
+                stringify!($ttype),
+                &self.expected_a(),
+            ))
         }
     };
 }
 
 macro_rules! impl_deserialize_primitive {
     ($ttype: ident, $visit_fn: ident, $deserialize_fn: ident) => {
-        impl Deserialize for $ttype {
-            fn deserialize<D: Deserializer>(des: D) -> Result<Self, AnyError> {
+        impl<'de> Deserialize<'de> for $ttype {
+            fn deserialize<D: Deserializer<'de>>(des: D) -> Result<Self, Error> {
                 struct PrimitiveVisitor {}
-                impl Visitor for PrimitiveVisitor {
+                impl<'de> Visitor<'de> for PrimitiveVisitor {
                     type Value = $ttype;
 
                     fn expected_a(self) -> String {
                         stringify!($ttype).to_string()
                     }
 
-                    fn $visit_fn(self, val: $ttype) -> Result<Self::Value, AnyError> {
+                    fn $visit_fn(self, val: $ttype) -> Result<Self::Value, Error> {
                         Ok(val)
                     }
                 }
@@ -49,16 +57,25 @@ macro_rules! impl_deserialize_primitive {
 }
 
 /// Implementors of this trait can be deserialized from any format
-pub trait Deserialize: Sized {
-    fn deserialize<D: Deserializer>(des: D) -> Result<Self, AnyError>;
+///
+/// *'de* is the lifetime of the input, types such as `&'de str` borrow from it instead of copying.
+pub trait Deserialize<'de>: Sized {
+    fn deserialize<D: Deserializer<'de>>(des: D) -> Result<Self, Error>;
 }
 
+/// Implementors can be deserialized from input of any lifetime, i.e. they never borrow from it
+pub trait DeserializeOwned: for<'de> Deserialize<'de> {}
+
+impl<T: for<'de> Deserialize<'de>> DeserializeOwned for T {}
+
 /// Parses bytes and delegates them to a visitor
-pub trait Deserializer: Sized {
-    fn deserialize_map<V: Visitor>(self, v: V) -> Result<V::Value, AnyError>;
-    fn deserialize_seq<V: Visitor>(self, v: V) -> Result<V::Value, AnyError>;
-    fn deserialize_struct<V: Visitor>(self, v: V) -> Result<V::Value, AnyError>;
-    fn deserialize_str<V: Visitor>(self, v: V) -> Result<V::Value, AnyError>;
+pub trait Deserializer<'de>: Sized {
+    fn deserialize_map<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error>;
+    fn deserialize_seq<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error>;
+    fn deserialize_struct<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error>;
+    fn deserialize_str<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error>;
+    /// Deserializes whatever the input contains, for self-describing formats only
+    fn deserialize_any<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error>;
 
     decl_deserialize_primitive!(deserialize_i8);
     decl_deserialize_primitive!(deserialize_i16);
@@ -74,21 +91,37 @@ pub trait Deserializer: Sized {
     decl_deserialize_primitive!(deserialize_f64);
     decl_deserialize_primitive!(deserialize_isize);
     decl_deserialize_primitive!(deserialize_usize);
+    decl_deserialize_primitive!(deserialize_bool);
+
+    /// Whether the input describes its own structure, e.g. names its fields
+    ///
+    /// Formats that do not, such as [binary], cannot [deserialize_any](Deserializer::deserialize_any)
+    /// and visit structs as the sequence of their fields in declaration order.
+    fn is_self_describing(&self) -> bool {
+        true
+    }
 }
 
 /// Maps a parsed value to a Rust type
-pub trait Visitor: Sized {
+pub trait Visitor<'de>: Sized {
     type Value;
     fn expected_a(self) -> String;
 
-    fn visit_map<M: MapAccess>(self, _map: M) -> Result<Self::Value, AnyError> {
-        Err(visiting_but_expected("map", &self.expected_a()).into())
+    fn visit_map<M: MapAccess<'de>>(self, _map: M) -> Result<Self::Value, Error> {
+        Err(visiting_but_expected("map", &self.expected_a()))
     }
-    fn visit_seq<S: SeqAccess>(self, _seq: S) -> Result<Self::Value, AnyError> {
-        Err(visiting_but_expected("seq", &self.expected_a()).into())
+    fn visit_seq<S: SeqAccess<'de>>(self, _seq: S) -> Result<Self::Value, Error> {
+        Err(visiting_but_expected("seq", &self.expected_a()))
     }
-    fn visit_str(self, _v: &str) -> Result<Self::Value, AnyError> {
-        Err(visiting_but_expected("str", &self.expected_a()).into())
+    fn visit_str(self, _v: &str) -> Result<Self::Value, Error> {
+        Err(visiting_but_expected("str", &self.expected_a()))
+    }
+    /// Visits a string that lives as long as the input, forwards to [visit_str](Visitor::visit_str) by default
+    fn visit_borrowed_str(self, v: &'de str) -> Result<Self::Value, Error> {
+        self.visit_str(v)
+    }
+    fn visit_null(self) -> Result<Self::Value, Error> {
+        Err(visiting_but_expected("null", &self.expected_a()))
     }
 
     decl_visit_primitive!(i8, visit_i8);
@@ -105,39 +138,92 @@ pub trait Visitor: Sized {
     decl_visit_primitive!(f64, visit_f64);
     decl_visit_primitive!(usize, visit_usize);
     decl_visit_primitive!(isize, visit_isize);
+    decl_visit_primitive!(bool, visit_bool);
 }
 
 /// Allows the access to key-value pairs
-pub trait MapAccess {
-    fn next_value<V: Deserialize>(&mut self) -> Result<V, AnyError>;
-    fn next_key<K: Deserialize>(&mut self) -> Result<Option<K>, AnyError>;
+pub trait MapAccess<'de> {
+    fn next_value<V: Deserialize<'de>>(&mut self) -> Result<V, Error>;
+    fn next_key<K: Deserialize<'de>>(&mut self) -> Result<Option<K>, Error>;
+
+    /// Reports an error the visitor could recover from, e.g. a missing or duplicate field
+    ///
+    /// By default the error is handed back so the visitor fails right away.
+    /// Deserializers that collect all errors store it instead and let the visitor carry on.
+    fn report(&mut self, err: Error) -> Result<(), Error> {
+        Err(err)
+    }
+
+    /// Like [next_value](MapAccess::next_value), but a value that fails to deserialize may be reported and skipped
+    ///
+    /// Returns *None* if the value was skipped.
+    fn next_value_or_report<V: Deserialize<'de>>(&mut self) -> Result<Option<V>, Error> {
+        self.next_value().map(Some)
+    }
 }
 
 /// Allows the access to sequences
-pub trait SeqAccess {
-    fn next_value<V: Deserialize>(&mut self) -> Result<Option<V>, AnyError>;
+pub trait SeqAccess<'de> {
+    fn next_value<V: Deserialize<'de>>(&mut self) -> Result<Option<V>, Error>;
 }
 
 // ##########################################
 
 mod error {
-    pub(crate) fn visiting_but_expected(etype: &str, gtype: &str) -> String {
-        "visiting a ".to_string() + etype + " but expected a " + gtype
+    use crate::error::Error;
+
+    pub(crate) fn visiting_but_expected(etype: &str, gtype: &str) -> Error {
+        Error::invalid_type(etype, gtype)
     }
 }
 
 // ##########################################
 
+/// Bytes read by [Peek::read_until_borrowed]
+pub enum Reference<'de> {
+    /// Borrowed directly from the input, lives as long as it
+    Borrowed(&'de [u8]),
+    /// Copied out of the input, e.g. because it was only held in a transient buffer
+    Copied(Vec<u8>),
+}
+
+impl<'de> Deref for Reference<'de> {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Reference::Borrowed(bytes) => bytes,
+            Reference::Copied(bytes) => bytes,
+        }
+    }
+}
+
+impl<'de> From<Reference<'de>> for Vec<u8> {
+    fn from(reference: Reference<'de>) -> Self {
+        match reference {
+            Reference::Borrowed(bytes) => bytes.to_vec(),
+            Reference::Copied(bytes) => bytes,
+        }
+    }
+}
+
 /// Utility functions for io::Read and io::Seek
-pub trait Peek {
-    fn peek(&mut self) -> Result<Option<u8>, AnyError>;
-    fn read_until(&mut self, end: &[u8]) -> Result<Vec<u8>, AnyError>;
-    fn consume(&mut self) -> Result<(), AnyError>;
-    fn consume_matching(&mut self, matches: &[u8]) -> Result<(), AnyError>;
+///
+/// *'de* is the lifetime of the input, only inputs held entirely in memory can hand out bytes borrowed from it.
+pub trait Peek<'de> {
+    fn peek(&mut self) -> Result<Option<u8>, Error>;
+    fn read_until(&mut self, end: &[u8]) -> Result<Vec<u8>, Error>;
+    fn consume(&mut self) -> Result<(), Error>;
+    fn consume_matching(&mut self, matches: &[u8]) -> Result<(), Error>;
+
+    /// Like [read_until](Peek::read_until) but borrows the bytes from the input if possible
+    fn read_until_borrowed(&mut self, end: &[u8]) -> Result<Reference<'de>, Error> {
+        self.read_until(end).map(Reference::Copied)
+    }
 }
 
-impl<R: io::Read + io::Seek> Peek for R {
-    fn peek(&mut self) -> Result<Option<u8>, AnyError> {
+impl<'de, R: io::Read + io::Seek> Peek<'de> for R {
+    fn peek(&mut self) -> Result<Option<u8>, Error> {
         let start = self.stream_position()?;
         let mut char: [u8; 1] = [0];
         let char = match self.read_exact(&mut char) {
@@ -146,59 +232,162 @@ impl<R: io::Read + io::Seek> Peek for R {
             Err(err) => Err(err),
         };
         self.seek(io::SeekFrom::Start(start))?;
-        char.map(|o| {
-            if o.is_some() {
-                return Some(*o.unwrap().get(0).unwrap());
-            }
-            return None;
-        })
-        .map_err(|e| e.into())
+        char.map(|o| o.map(|c| c[0])).map_err(Error::from)
     }
 
-    fn consume(&mut self) -> Result<(), AnyError> {
+    fn consume(&mut self) -> Result<(), Error> {
         self.seek(io::SeekFrom::Current(1))?;
         Ok(())
     }
 
-    fn consume_matching(&mut self, matches: &[u8]) -> Result<(), AnyError> {
+    fn consume_matching(&mut self, matches: &[u8]) -> Result<(), Error> {
         loop {
-            let char = self.peek()?;
-            if char.is_some() && matches.contains(&char.unwrap()) {
-                self.consume()?;
-            } else {
-                break;
+            match self.peek()? {
+                Some(char) if matches.contains(&char) => self.consume()?,
+                _ => break,
             }
         }
         Ok(())
     }
 
-    fn read_until(&mut self, end: &[u8]) -> Result<Vec<u8>, AnyError> {
+    fn read_until(&mut self, end: &[u8]) -> Result<Vec<u8>, Error> {
         let mut vec = vec![];
         loop {
-            let char = self.peek()?;
-            if char.is_some() && !end.contains(&char.unwrap()) {
-                self.consume()?;
-                vec.push(char.unwrap());
-            } else {
-                break;
+            match self.peek()? {
+                Some(char) if !end.contains(&char) => {
+                    self.consume()?;
+                    vec.push(char);
+                }
+                _ => break,
             }
         }
         Ok(vec)
     }
 }
 
+/// [Peek] directly into a byte slice
+///
+/// Unlike the implementation for [io::Read] + [io::Seek], peeking and consuming are plain index operations.
+/// Prefer it whenever the whole input is already in memory.
+pub struct SliceRead<'a> {
+    slice: &'a [u8],
+    index: usize,
+}
+
+impl<'a> SliceRead<'a> {
+    pub fn new(slice: &'a [u8]) -> Self {
+        Self { slice, index: 0 }
+    }
+}
+
+impl<'de> Peek<'de> for SliceRead<'de> {
+    fn peek(&mut self) -> Result<Option<u8>, Error> {
+        Ok(self.slice.get(self.index).copied())
+    }
+
+    fn consume(&mut self) -> Result<(), Error> {
+        self.index = (self.index + 1).min(self.slice.len());
+        Ok(())
+    }
+
+    fn consume_matching(&mut self, matches: &[u8]) -> Result<(), Error> {
+        while self
+            .slice
+            .get(self.index)
+            .is_some_and(|char| matches.contains(char))
+        {
+            self.index += 1;
+        }
+        Ok(())
+    }
+
+    fn read_until(&mut self, end: &[u8]) -> Result<Vec<u8>, Error> {
+        self.read_until_borrowed(end).map(Vec::from)
+    }
+
+    fn read_until_borrowed(&mut self, end: &[u8]) -> Result<Reference<'de>, Error> {
+        let rest = &self.slice[self.index..];
+        let len = rest
+            .iter()
+            .position(|char| end.contains(char))
+            .unwrap_or(rest.len());
+        self.index += len;
+        Ok(Reference::Borrowed(&rest[..len]))
+    }
+}
+
+/// [Peek] into any [io::Read], including non-seekable ones such as stdin, sockets or decompression streams
+///
+/// The input is buffered, the buffer doubles as the one byte lookahead [Peek] requires.
+pub struct IoRead<R: io::Read> {
+    read: io::BufReader<R>,
+}
+
+impl<R: io::Read> IoRead<R> {
+    pub fn new(read: R) -> Self {
+        Self {
+            read: io::BufReader::new(read),
+        }
+    }
+}
+
+impl<'de, R: io::Read> Peek<'de> for IoRead<R> {
+    fn peek(&mut self) -> Result<Option<u8>, Error> {
+        Ok(self.read.fill_buf()?.first().copied())
+    }
+
+    fn consume(&mut self) -> Result<(), Error> {
+        if !self.read.fill_buf()?.is_empty() {
+            BufRead::consume(&mut self.read, 1);
+        }
+        Ok(())
+    }
+
+    fn consume_matching(&mut self, matches: &[u8]) -> Result<(), Error> {
+        loop {
+            let buffer = self.read.fill_buf()?;
+            let len = buffer
+                .iter()
+                .position(|char| !matches.contains(char))
+                .unwrap_or(buffer.len());
+            let done = len < buffer.len() || buffer.is_empty();
+            BufRead::consume(&mut self.read, len);
+            if done {
+                return Ok(());
+            }
+        }
+    }
+
+    fn read_until(&mut self, end: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut vec = vec![];
+        loop {
+            let buffer = self.read.fill_buf()?;
+            let len = buffer
+                .iter()
+                .position(|char| end.contains(char))
+                .unwrap_or(buffer.len());
+            let done = len < buffer.len() || buffer.is_empty();
+            vec.extend_from_slice(&buffer[..len]);
+            BufRead::consume(&mut self.read, len);
+            if done {
+                return Ok(vec);
+            }
+        }
+    }
+}
+
 // ##########################################
-impl Deserialize for String {
-    fn deserialize<D: Deserializer>(des: D) -> Result<Self, AnyError> {
+impl<'de> Deserialize<'de> for String {
+    fn deserialize<D: Deserializer<'de>>(des: D) -> Result<Self, Error> {
         struct StringVisitor {}
-        impl Visitor for StringVisitor {
+        impl<'de> Visitor<'de> for StringVisitor {
             type Value = String;
 
             fn expected_a(self) -> String {
                 "string".to_string()
             }
 
-            fn visit_str(self, v: &str) -> Result<Self::Value, AnyError> {
+            fn visit_str(self, v: &str) -> Result<Self::Value, Error> {
                 Ok(v.to_string())
             }
         }
@@ -207,19 +396,70 @@ impl Deserialize for String {
     }
 }
 
-impl<I: Deserialize> Deserialize for Vec<I> {
-    fn deserialize<D: Deserializer>(des: D) -> Result<Self, AnyError> {
+/// Borrows the string from the input
+///
+/// Fails for strings that cannot be borrowed, i.e. strings containing escape sequences
+/// or strings read from a stream rather than from memory. Use [Cow] to accept both.
+impl<'de: 'a, 'a> Deserialize<'de> for &'a str {
+    fn deserialize<D: Deserializer<'de>>(des: D) -> Result<Self, Error> {
+        struct StrVisitor {}
+        impl<'de> Visitor<'de> for StrVisitor {
+            type Value = &'de str;
+
+            fn expected_a(self) -> String {
+                "borrowed str".to_string()
+            }
+
+            fn visit_str(self, _v: &str) -> Result<Self::Value, Error> {
+                Err(visiting_but_expected("transient str", &self.expected_a()))
+            }
+
+            fn visit_borrowed_str(self, v: &'de str) -> Result<Self::Value, Error> {
+                Ok(v)
+            }
+        }
+
+        des.deserialize_str(StrVisitor {})
+    }
+}
+
+/// Borrows the string from the input whenever possible, copies it otherwise
+impl<'de: 'a, 'a> Deserialize<'de> for Cow<'a, str> {
+    fn deserialize<D: Deserializer<'de>>(des: D) -> Result<Self, Error> {
+        struct CowVisitor {}
+        impl<'de> Visitor<'de> for CowVisitor {
+            type Value = Cow<'de, str>;
+
+            fn expected_a(self) -> String {
+                "string".to_string()
+            }
+
+            fn visit_str(self, v: &str) -> Result<Self::Value, Error> {
+                Ok(Cow::Owned(v.to_string()))
+            }
+
+            fn visit_borrowed_str(self, v: &'de str) -> Result<Self::Value, Error> {
+                Ok(Cow::Borrowed(v))
+            }
+        }
+
+        des.deserialize_str(CowVisitor {})
+    }
+}
+
+impl<'de, I: Deserialize<'de>> Deserialize<'de> for Vec<I> {
+    fn deserialize<D: Deserializer<'de>>(des: D) -> Result<Self, Error> {
         struct VecVisitor<I> {
             marker: PhantomData<I>,
         }
-        impl<I: Deserialize> Visitor for VecVisitor<I> {
+        impl<'de, I: Deserialize<'de>> Visitor<'de> for VecVisitor<I> {
             type Value = Vec<I>;
 
             fn expected_a(self) -> String {
                 "vec".to_string()
             }
 
-            fn visit_seq<S: SeqAccess>(self, mut seq: S) -> Result<Self::Value, AnyError> {
+            fn visit_seq<S: SeqAccess<'de>>(self, mut seq: S) -> Result<Self::Value, Error> {
                 let mut vec = vec![];
 
                 while let Some(item) = seq.next_value()? {
@@ -236,20 +476,22 @@ impl<I: Deserialize> Deserialize for Vec<I> {
     }
 }
 
-impl<K: Deserialize + Hash + Eq, V: Deserialize> Deserialize for HashMap<K, V> {
-    fn deserialize<D: Deserializer>(des: D) -> Result<Self, AnyError> {
+impl<'de, K: Deserialize<'de> + Hash + Eq, V: Deserialize<'de>> Deserialize<'de> for HashMap<K, V> {
+    fn deserialize<D: Deserializer<'de>>(des: D) -> Result<Self, Error> {
         struct HashMapVisitor<K, V> {
             k_marker: PhantomData<K>,
             v_marker: PhantomData<V>,
         }
-        impl<K: Deserialize + Hash + Eq, V: Deserialize> Visitor for HashMapVisitor<K, V> {
+        impl<'de, K: Deserialize<'de> + Hash + Eq, V: Deserialize<'de>> Visitor<'de>
+            for HashMapVisitor<K, V>
+        {
             type Value = HashMap<K, V>;
 
             fn expected_a(self) -> String {
                 "hashmap".to_string()
             }
 
-            fn visit_map<M: MapAccess>(self, mut map: M) -> Result<Self::Value, AnyError> {
+            fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, Error> {
                 let mut tmp = HashMap::new();
 
                 while let Some(key) = map.next_key()? {
@@ -282,3 +524,4 @@ impl_deserialize_primitive!(f32, visit_f32, deserialize_f32);
 impl_deserialize_primitive!(f64, visit_f64, deserialize_f64);
 impl_deserialize_primitive!(isize, visit_isize, deserialize_isize);
 impl_deserialize_primitive!(usize, visit_usize, deserialize_usize);
+impl_deserialize_primitive!(bool, visit_bool, deserialize_bool);
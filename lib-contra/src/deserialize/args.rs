@@ -0,0 +1,543 @@
+//! Deserialization from command line arguments
+//!
+//! `--graphics.width 1920` sets the nested field `graphics.width`, `--width=1920` works as well.
+//! A flag without a value, e.g. `--fullscreen`, is `true`.
+//! Repeating a flag (`--admin alice --admin bob`) or separating values by commas (`--admin alice,bob`) fills sequences.
+use std::{
+    any::type_name,
+    cell::RefCell,
+    collections::{btree_map, BTreeMap},
+    fmt::Write,
+    rc::Rc,
+    slice,
+    str::FromStr,
+    vec,
+};
+
+use crate::{error::Error, value::Value};
+
+use super::{
+    env::split_list, Deserialize, DeserializeOwned, Deserializer, MapAccess, SeqAccess, Visitor,
+};
+
+/// Deserializes *T* from *args*, the first of which is the name of the program as in [std::env::args]
+///
+/// `--help` or `-h` in place of a flag result in an [Error::Help] listing the fields of *T*,
+/// as the value of a flag (`--name -h`) they are just a value.
+///
+/// # Example
+/// ```
+/// use std::collections::HashMap;
+/// use lib_contra::{deserialize::args::from_args, error::Error};
+///
+/// let args = ["game", "--graphics.width", "1920", "--graphics.height=1080"].map(String::from);
+/// let config: HashMap<String, HashMap<String, u32>> = from_args(args).unwrap();
+/// assert_eq!(config["graphics"]["height"], 1080);
+///
+/// let help = from_args::<HashMap<String, u32>>(["game", "--help"].map(String::from));
+/// assert!(matches!(help, Err(Error::Help(_))));
+///
+/// // as called by a program
+/// let _ = from_args::<HashMap<String, u32>>(std::env::args());
+/// ```
+pub fn from_args<T: DeserializeOwned>(args: impl IntoIterator<Item = String>) -> Result<T, Error> {
+    let mut args = args.into_iter();
+    let program = args.next().unwrap_or_default();
+    match ArgsDeserializer::parse(args) {
+        Err(Error::Help(_)) => Err(Error::Help(help::<T>(&program))),
+        parsed => T::deserialize(parsed?),
+    }
+}
+
+/// The arguments, nested by their dotted keys
+#[derive(Debug, Clone)]
+enum Node {
+    /// Every value given for the key, in order
+    Values(Vec<String>),
+    Table(BTreeMap<String, Node>),
+}
+
+/// Maps command line arguments onto any [Deserialize]
+///
+/// If a flag is given more than once for a single value, the last one wins.
+#[derive(Debug, Clone)]
+pub struct ArgsDeserializer {
+    node: Node,
+    /// The flag, e.g. `--graphics.width`
+    name: String,
+}
+
+impl ArgsDeserializer {
+    /// Parses the arguments without the program name
+    ///
+    /// `--help` or `-h` in place of a flag result in an empty [Error::Help], even after other errors.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, Error> {
+        let mut root = BTreeMap::new();
+        let mut unexpected = None;
+        let mut args = args.into_iter().peekable();
+        while let Some(arg) = args.next() {
+            let flag = match arg.strip_prefix("--") {
+                _ if arg == "--help" || arg == "-h" => return Err(Error::Help(String::new())),
+                Some(flag) if !flag.is_empty() => flag,
+                _ => {
+                    unexpected.get_or_insert(arg);
+                    continue;
+                }
+            };
+            let (key, value) = match flag.split_once('=') {
+                Some((key, value)) => (key, value.to_string()),
+                None => match args.peek() {
+                    Some(value) if !value.starts_with("--") => {
+                        (flag, args.next().unwrap_or_default())
+                    }
+                    _ => (flag, "true".to_string()),
+                },
+            };
+            let path: Vec<&str> = key.split('.').collect();
+            insert(&mut root, &path, value);
+        }
+        if let Some(arg) = unexpected {
+            return Err(Error::syntax(format!("unexpected argument `{}`", arg)));
+        }
+        Ok(Self {
+            node: Node::Table(root),
+            name: "--".to_string(),
+        })
+    }
+
+    fn child(&self, key: &str, node: Node) -> Self {
+        let name = match self.name.as_str() {
+            "--" => format!("--{}", key),
+            name => format!("{}.{}", name, key),
+        };
+        Self { node, name }
+    }
+
+    fn value(&self, expected: &str) -> Result<&str, Error> {
+        match &self.node {
+            Node::Values(values) => Ok(values.last().map(String::as_str).unwrap_or_default()),
+            Node::Table(_) => Err(Error::invalid_type("map", expected)),
+        }
+    }
+
+    fn parse_value<T: FromStr>(&self) -> Result<T, Error> {
+        let value = self.value(type_name::<T>())?;
+        value
+            .parse()
+            .map_err(|_| self.invalid_value(value, type_name::<T>()))
+    }
+
+    fn invalid_value(&self, value: &str, expected: &str) -> Error {
+        Error::invalid_value(format!("`{}` for `{}`", value, self.name), expected)
+    }
+}
+
+fn insert(table: &mut BTreeMap<String, Node>, path: &[&str], value: String) {
+    match path {
+        [] => {}
+        // nested flags take precedence over a plain one
+        [key] => match table
+            .entry(key.to_string())
+            .or_insert_with(|| Node::Values(vec![]))
+        {
+            Node::Values(values) => values.push(value),
+            Node::Table(_) => {}
+        },
+        [key, rest @ ..] => {
+            let node = table
+                .entry(key.to_string())
+                .or_insert_with(|| Node::Table(BTreeMap::new()));
+            if let Node::Values(_) = node {
+                *node = Node::Table(BTreeMap::new());
+            }
+            if let Node::Table(table) = node {
+                insert(table, rest, value);
+            }
+        }
+    }
+}
+
+macro_rules! impl_deserialize_parsed {
+    ($ttype: ident, $deserialize_fn: ident, $visit_fn: ident) => {
+        fn $deserialize_fn<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+            v.$visit_fn(self.parse_value::<$ttype>()?)
+        }
+    };
+}
+
+impl<'de> Deserializer<'de> for ArgsDeserializer {
+    fn deserialize_map<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        match self.node {
+            Node::Table(table) => v.visit_map(ArgsMap {
+                parent: ArgsDeserializer {
+                    node: Node::Table(BTreeMap::new()),
+                    name: self.name,
+                },
+                iter: table.into_iter(),
+                value: None,
+            }),
+            Node::Values(_) => Err(Error::invalid_type(&format!("`{}`", self.name), "map")),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        let values = match self.node {
+            Node::Values(values) if values.len() == 1 => split_list(&values[0]),
+            Node::Values(values) => values,
+            Node::Table(_) => return Err(Error::invalid_type("map", "seq")),
+        };
+        let name = self.name;
+        v.visit_seq(ArgsSeq {
+            iter: values
+                .into_iter()
+                .map(|value| ArgsDeserializer {
+                    node: Node::Values(vec![value]),
+                    name: name.clone(),
+                })
+                .collect::<Vec<_>>()
+                .into_iter(),
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        self.deserialize_map(v)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        v.visit_str(self.value("str")?)
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        match self.node {
+            Node::Values(_) => self.deserialize_str(v),
+            Node::Table(_) => self.deserialize_map(v),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        let value = self.value("bool")?;
+        match value.to_lowercase().as_str() {
+            "true" | "1" => v.visit_bool(true),
+            "false" | "0" => v.visit_bool(false),
+            _ => Err(self.invalid_value(value, "bool")),
+        }
+    }
+
+    impl_deserialize_parsed!(i8, deserialize_i8, visit_i8);
+    impl_deserialize_parsed!(i16, deserialize_i16, visit_i16);
+    impl_deserialize_parsed!(i32, deserialize_i32, visit_i32);
+    impl_deserialize_parsed!(i64, deserialize_i64, visit_i64);
+    impl_deserialize_parsed!(i128, deserialize_i128, visit_i128);
+    impl_deserialize_parsed!(isize, deserialize_isize, visit_isize);
+    impl_deserialize_parsed!(u8, deserialize_u8, visit_u8);
+    impl_deserialize_parsed!(u16, deserialize_u16, visit_u16);
+    impl_deserialize_parsed!(u32, deserialize_u32, visit_u32);
+    impl_deserialize_parsed!(u64, deserialize_u64, visit_u64);
+    impl_deserialize_parsed!(u128, deserialize_u128, visit_u128);
+    impl_deserialize_parsed!(usize, deserialize_usize, visit_usize);
+    impl_deserialize_parsed!(f32, deserialize_f32, visit_f32);
+    impl_deserialize_parsed!(f64, deserialize_f64, visit_f64);
+}
+
+struct ArgsMap {
+    /// Names the children of the map
+    parent: ArgsDeserializer,
+    iter: btree_map::IntoIter<String, Node>,
+    value: Option<ArgsDeserializer>,
+}
+
+impl<'de> MapAccess<'de> for ArgsMap {
+    fn next_value<V: Deserialize<'de>>(&mut self) -> Result<V, Error> {
+        match self.value.take() {
+            Some(value) => V::deserialize(value),
+            None => Err(Error::custom("requested a map value before its key")),
+        }
+    }
+
+    fn next_key<K: Deserialize<'de>>(&mut self) -> Result<Option<K>, Error> {
+        match self.iter.next() {
+            Some((key, node)) => {
+                self.value = Some(self.parent.child(&key, node));
+                K::deserialize(Value::String(key)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+struct ArgsSeq {
+    iter: vec::IntoIter<ArgsDeserializer>,
+}
+
+impl<'de> SeqAccess<'de> for ArgsSeq {
+    fn next_value<V: Deserialize<'de>>(&mut self) -> Result<Option<V>, Error> {
+        self.iter.next().map(V::deserialize).transpose()
+    }
+}
+
+// ##########################################
+
+/// The help text listing every flag *T* accepts
+///
+/// The flags are discovered by driving the [Deserialize] implementation of *T* with placeholder input:
+/// the field names are taken from the [Error::UnknownField] a derived struct reports for an unknown key,
+/// the names of enum variants from the [Error::InvalidValue] a derived enum reports for an unknown variant.
+pub fn help<T: DeserializeOwned>(program: &str) -> String {
+    let flags = Rc::new(RefCell::new(vec![]));
+    let _ = T::deserialize(Probe {
+        name: "--".to_string(),
+        repeated: false,
+        flags: flags.clone(),
+    });
+
+    let mut help = format!("Usage: {} [OPTIONS]\n\nOptions:\n", program);
+    for flag in flags.borrow().iter() {
+        let value = match (flag.kind.as_str(), flag.repeated) {
+            ("bool", false) => String::new(),
+            (kind, false) => format!(" <{}>", kind),
+            (kind, true) => format!(" <{}>...", kind),
+        };
+        writeln!(help, "  {}{}", flag.name, value).unwrap();
+    }
+    writeln!(help, "  -h, --help  Print this help").unwrap();
+    help
+}
+
+/// A flag discovered by [help]
+struct Flag {
+    name: String,
+    kind: String,
+    repeated: bool,
+}
+
+/// Records the flags a [Deserialize] implementation asks for, never produces a value
+struct Probe {
+    name: String,
+    repeated: bool,
+    flags: Rc<RefCell<Vec<Flag>>>,
+}
+
+/// Deeper nesting is not listed, it guards against recursive types
+const PROBE_DEPTH: usize = 8;
+
+impl Probe {
+    fn child(&self, key: &str) -> Self {
+        let name = match self.name.as_str() {
+            "--" => format!("--{}", key),
+            name => format!("{}.{}", name, key),
+        };
+        Self {
+            name,
+            repeated: false,
+            flags: self.flags.clone(),
+        }
+    }
+
+    fn record(&self, kind: &str) -> Error {
+        self.flags.borrow_mut().push(Flag {
+            name: self.name.clone(),
+            kind: kind.to_string(),
+            repeated: self.repeated,
+        });
+        probed()
+    }
+}
+
+fn probed() -> Error {
+    Error::custom("probed")
+}
+
+macro_rules! impl_probe_primitive {
+    ($ttype: ident, $deserialize_fn: ident) => {
+        fn $deserialize_fn<V: Visitor<'de>>(self, _v: V) -> Result<V::Value, Error> {
+            Err(self.record(stringify!($ttype)))
+        }
+    };
+}
+
+impl<'de> Deserializer<'de> for Probe {
+    fn deserialize_map<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        if self.name.matches('.').count() >= PROBE_DEPTH {
+            return Err(probed());
+        }
+        v.visit_map(ProbeMap {
+            probe: self,
+            fields: None,
+            key: None,
+        })
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        v.visit_seq(ProbeSeq {
+            probe: Some(Probe {
+                repeated: true,
+                ..self
+            }),
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        self.deserialize_map(v)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        match v.visit_str("") {
+            Err(Error::InvalidValue { expected, .. }) => Err(self.record(&expected)),
+            _ => Err(self.record("str")),
+        }
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, _v: V) -> Result<V::Value, Error> {
+        Err(self.record("value"))
+    }
+
+    impl_probe_primitive!(i8, deserialize_i8);
+    impl_probe_primitive!(i16, deserialize_i16);
+    impl_probe_primitive!(i32, deserialize_i32);
+    impl_probe_primitive!(i64, deserialize_i64);
+    impl_probe_primitive!(i128, deserialize_i128);
+    impl_probe_primitive!(isize, deserialize_isize);
+    impl_probe_primitive!(u8, deserialize_u8);
+    impl_probe_primitive!(u16, deserialize_u16);
+    impl_probe_primitive!(u32, deserialize_u32);
+    impl_probe_primitive!(u64, deserialize_u64);
+    impl_probe_primitive!(u128, deserialize_u128);
+    impl_probe_primitive!(usize, deserialize_usize);
+    impl_probe_primitive!(f32, deserialize_f32);
+    impl_probe_primitive!(f64, deserialize_f64);
+    impl_probe_primitive!(bool, deserialize_bool);
+}
+
+struct ProbeMap {
+    probe: Probe,
+    /// The fields of a struct, *None* until they are known
+    fields: Option<slice::Iter<'static, &'static str>>,
+    key: Option<String>,
+}
+
+/// Stands in for the keys of maps that accept any key
+const ANY_KEY: &str = "<key>";
+
+impl<'de> MapAccess<'de> for ProbeMap {
+    fn next_value<V: Deserialize<'de>>(&mut self) -> Result<V, Error> {
+        match self.key.take() {
+            Some(key) => V::deserialize(self.probe.child(&key)),
+            None => Err(probed()),
+        }
+    }
+
+    fn next_key<K: Deserialize<'de>>(&mut self) -> Result<Option<K>, Error> {
+        if self.fields.is_none() {
+            match K::deserialize(Value::String(ANY_KEY.to_string())) {
+                Err(Error::UnknownField { expected, .. }) => self.fields = Some(expected.iter()),
+                Ok(key) => {
+                    // a map without fixed fields, probed once for its values
+                    self.fields = Some([].iter());
+                    self.key = Some(ANY_KEY.to_string());
+                    return Ok(Some(key));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        match self.fields.as_mut().and_then(Iterator::next) {
+            Some(field) => {
+                self.key = Some(field.to_string());
+                K::deserialize(Value::String(field.to_string())).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn report(&mut self, _err: Error) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn next_value_or_report<V: Deserialize<'de>>(&mut self) -> Result<Option<V>, Error> {
+        // the probe never produces a value, skipping it moves on to the next field
+        Ok(self.next_value().ok())
+    }
+}
+
+struct ProbeSeq {
+    probe: Option<Probe>,
+}
+
+impl<'de> SeqAccess<'de> for ProbeSeq {
+    fn next_value<V: Deserialize<'de>>(&mut self) -> Result<Option<V>, Error> {
+        match self.probe.take() {
+            Some(probe) => V::deserialize(probe).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        std::iter::once("game")
+            .chain(args.iter().copied())
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn flags_values_and_repetitions() {
+        let parsed: HashMap<String, Vec<String>> = from_args(args(&[
+            "--admin",
+            "alice",
+            "--admin=bob",
+            "--fullscreen",
+            "--tags",
+            "a,b",
+        ]))
+        .unwrap();
+        assert_eq!(parsed["admin"], vec!["alice", "bob"]);
+        assert_eq!(parsed["fullscreen"], vec!["true"]);
+        assert_eq!(parsed["tags"], vec!["a", "b"]);
+
+        let flags: HashMap<String, bool> =
+            from_args(args(&["--fullscreen", "--vsync", "false"])).unwrap();
+        assert!(flags["fullscreen"]);
+        assert!(!flags["vsync"]);
+    }
+
+    #[test]
+    fn errors_name_the_flag() {
+        let err = from_args::<HashMap<String, u32>>(args(&["--width", "wide"])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid value: found `wide` for `--width`, expected u32"
+        );
+
+        let err = from_args::<HashMap<String, u32>>(args(&["1920"])).unwrap_err();
+        assert!(matches!(err, Error::Syntax(_)));
+    }
+
+    #[test]
+    fn help_lists_map_values() {
+        let help = help::<HashMap<String, Vec<u16>>>("game");
+        assert_eq!(
+            help,
+            "Usage: game [OPTIONS]\n\nOptions:\n  --<key> <u16>...\n  -h, --help  Print this help\n"
+        );
+    }
+
+    #[test]
+    fn help_only_in_place_of_a_flag() {
+        let parsed: HashMap<String, String> = from_args(args(&["--name", "-h"])).unwrap();
+        assert_eq!(parsed["name"], "-h");
+
+        for help in [
+            &["--name", "x", "-h"][..],
+            &["--fullscreen", "--help"],
+            &["1920", "-h"],
+        ] {
+            let err = from_args::<HashMap<String, String>>(args(help)).unwrap_err();
+            assert!(matches!(err, Error::Help(text) if text.contains("-h, --help")));
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Deserialization of the compact binary format, see [serialize::binary](crate::serialize::binary)
+use std::str::from_utf8;
+
+use crate::{error::Error, serialize::binary::IntEncoding};
+
+use super::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+
+/// Deserializes *bytes* written with [varint](IntEncoding::Varint) integers
+///
+/// Strings are borrowed from *bytes*, e.g. as `&'de str`.
+pub fn from_bytes<'de, D: Deserialize<'de>>(bytes: &'de [u8]) -> Result<D, Error> {
+    from_bytes_with(bytes, IntEncoding::default())
+}
+
+pub fn from_bytes_with<'de, D: Deserialize<'de>>(
+    bytes: &'de [u8],
+    ints: IntEncoding,
+) -> Result<D, Error> {
+    let mut de = BinaryDeserializer::new(bytes, ints);
+    let value = D::deserialize(&mut de)?;
+    match de.input.len() {
+        0 => Ok(value),
+        len => Err(Error::syntax(format!("{} trailing bytes", len))),
+    }
+}
+
+pub struct BinaryDeserializer<'de> {
+    input: &'de [u8],
+    ints: IntEncoding,
+}
+
+impl<'de> BinaryDeserializer<'de> {
+    pub fn new(input: &'de [u8], ints: IntEncoding) -> Self {
+        Self { input, ints }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'de [u8], Error> {
+        if len > self.input.len() {
+            return Err(Error::syntax(format!(
+                "unexpected end of input, expected {} more bytes",
+                len - self.input.len()
+            )));
+        }
+        let (taken, rest) = self.input.split_at(len);
+        self.input = rest;
+        Ok(taken)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn varint(&mut self) -> Result<u128, Error> {
+        let mut value = 0u128;
+        for shift in (0..128).step_by(7) {
+            let [byte] = self.take_array()?;
+            value |= ((byte & 0x7f) as u128) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(Error::syntax("varint longer than 128 bits"))
+    }
+
+    fn signed_varint(&mut self) -> Result<i128, Error> {
+        let zigzag = self.varint()?;
+        Ok((zigzag >> 1) as i128 ^ -((zigzag & 1) as i128))
+    }
+
+    fn len(&mut self) -> Result<usize, Error> {
+        let len = match self.ints {
+            IntEncoding::Fixed => u64::from_le_bytes(self.take_array()?) as u128,
+            IntEncoding::Varint => self.varint()?,
+        };
+        usize::try_from(len).map_err(|_| Error::invalid_value(len, "a length"))
+    }
+}
+
+macro_rules! impl_deserialize_bytes {
+    ($type: ident, $deserialize_fn: ident, $visit_fn: ident) => {
+        fn $deserialize_fn<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+            v.$visit_fn($type::from_le_bytes(self.take_array()?))
+        }
+    };
+}
+
+macro_rules! impl_deserialize_int {
+    ($type: ident, $varint_fn: ident, $deserialize_fn: ident, $visit_fn: ident) => {
+        fn $deserialize_fn<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+            let value = match self.ints {
+                IntEncoding::Fixed => $type::from_le_bytes(self.take_array()?),
+                IntEncoding::Varint => {
+                    let value = self.$varint_fn()?;
+                    $type::try_from(value)
+                        .map_err(|_| Error::invalid_value(value, stringify!($type)))?
+                }
+            };
+            v.$visit_fn(value)
+        }
+    };
+}
+
+impl<'de> Deserializer<'de> for &mut BinaryDeserializer<'de> {
+    fn deserialize_map<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        let remaining = self.len()?;
+        v.visit_map(BinaryMap {
+            de: self,
+            remaining,
+        })
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        let remaining = self.len()?;
+        v.visit_seq(BinarySeq {
+            de: self,
+            remaining: Some(remaining),
+        })
+    }
+
+    /// Structs are visited as the sequence of their fields, which the visitor reads as many as it has
+    fn deserialize_struct<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        v.visit_seq(BinarySeq {
+            de: self,
+            remaining: None,
+        })
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        let len = self.len()?;
+        v.visit_borrowed_str(from_utf8(self.take(len)?)?)
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        Err(Error::invalid_type(
+            "binary data, which is not self-describing",
+            &v.expected_a(),
+        ))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        match self.take_array()? {
+            [0] => v.visit_bool(false),
+            [1] => v.visit_bool(true),
+            [byte] => Err(Error::invalid_value(byte, "bool")),
+        }
+    }
+
+    fn deserialize_usize<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        let value = u64::deserialize(&mut *self)?;
+        v.visit_usize(usize::try_from(value).map_err(|_| Error::invalid_value(value, "usize"))?)
+    }
+
+    fn deserialize_isize<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        let value = i64::deserialize(&mut *self)?;
+        v.visit_isize(isize::try_from(value).map_err(|_| Error::invalid_value(value, "isize"))?)
+    }
+
+    fn is_self_describing(&self) -> bool {
+        false
+    }
+
+    impl_deserialize_bytes!(u8, deserialize_u8, visit_u8);
+    impl_deserialize_bytes!(i8, deserialize_i8, visit_i8);
+    impl_deserialize_bytes!(f32, deserialize_f32, visit_f32);
+    impl_deserialize_bytes!(f64, deserialize_f64, visit_f64);
+    impl_deserialize_int!(u16, varint, deserialize_u16, visit_u16);
+    impl_deserialize_int!(u32, varint, deserialize_u32, visit_u32);
+    impl_deserialize_int!(u64, varint, deserialize_u64, visit_u64);
+    impl_deserialize_int!(u128, varint, deserialize_u128, visit_u128);
+    impl_deserialize_int!(i16, signed_varint, deserialize_i16, visit_i16);
+    impl_deserialize_int!(i32, signed_varint, deserialize_i32, visit_i32);
+    impl_deserialize_int!(i64, signed_varint, deserialize_i64, visit_i64);
+    impl_deserialize_int!(i128, signed_varint, deserialize_i128, visit_i128);
+}
+
+struct BinarySeq<'a, 'de> {
+    de: &'a mut BinaryDeserializer<'de>,
+    /// *None* for the fields of a struct, which are read as long as the visitor asks for them
+    remaining: Option<usize>,
+}
+
+impl<'a, 'de> SeqAccess<'de> for BinarySeq<'a, 'de> {
+    fn next_value<V: Deserialize<'de>>(&mut self) -> Result<Option<V>, Error> {
+        match &mut self.remaining {
+            Some(0) => return Ok(None),
+            Some(remaining) => *remaining -= 1,
+            None => {}
+        }
+        V::deserialize(&mut *self.de).map(Some)
+    }
+}
+
+struct BinaryMap<'a, 'de> {
+    de: &'a mut BinaryDeserializer<'de>,
+    remaining: usize,
+}
+
+impl<'a, 'de> MapAccess<'de> for BinaryMap<'a, 'de> {
+    fn next_value<V: Deserialize<'de>>(&mut self) -> Result<V, Error> {
+        V::deserialize(&mut *self.de)
+    }
+
+    fn next_key<K: Deserialize<'de>>(&mut self) -> Result<Option<K>, Error> {
+        match self.remaining {
+            0 => Ok(None),
+            _ => {
+                self.remaining -= 1;
+                K::deserialize(&mut *self.de).map(Some)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::serialize::binary::{to_bytes, to_bytes_with};
+
+    #[test]
+    fn round_trips_in_both_encodings() {
+        let values = vec![i64::MIN, -1, 0, 1, 300, i64::MAX];
+        for ints in [IntEncoding::Fixed, IntEncoding::Varint] {
+            let bytes = to_bytes_with(&values, ints).unwrap();
+            assert_eq!(from_bytes_with::<Vec<i64>>(&bytes, ints).unwrap(), values);
+        }
+        let bytes = to_bytes(&vec!["borrowed"]).unwrap();
+        assert_eq!(from_bytes::<Vec<&str>>(&bytes).unwrap(), vec!["borrowed"]);
+        assert_eq!(from_bytes::<f64>(&to_bytes(&1.5f64).unwrap()).unwrap(), 1.5);
+    }
+
+    #[test]
+    fn maps_are_length_prefixed_pairs() {
+        let bytes = [1, 1, b'a', 7];
+        let map: HashMap<String, u8> = from_bytes(&bytes).unwrap();
+        assert_eq!(map["a"], 7);
+    }
+
+    #[test]
+    fn malformed_input_is_an_error() {
+        assert!(from_bytes::<u32>(&[0x80]).is_err());
+        assert!(from_bytes::<u8>(&[1, 2]).is_err());
+        assert!(from_bytes::<u8>(&[0xac, 0x02]).is_err());
+        assert!(from_bytes::<bool>(&[2]).is_err());
+        assert!(from_bytes::<String>(&[2, 0xff, 0xfe]).is_err());
+        assert!(from_bytes::<crate::value::Value>(&[0]).is_err());
+    }
+}
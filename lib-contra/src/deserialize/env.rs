@@ -0,0 +1,507 @@
+//! Deserialization from environment variables
+//!
+//! A variable such as `GAME_GRAPHICS__WIDTH=1920` is split into the prefix `GAME_` and the
+//! field path `graphics.width`: `__` separates nested fields, single underscores are part of the field name.
+//! Names are matched in lower case.
+use std::{
+    any::type_name,
+    collections::{btree_map, BTreeMap},
+    str::FromStr,
+    vec,
+};
+
+use crate::{
+    error::Error,
+    layered::Source,
+    value::{parse_bool, Value},
+};
+
+use super::{Deserialize, DeserializeOwned, Deserializer, MapAccess, SeqAccess, Visitor};
+
+/// Separates the fields of nested structs within a variable name
+pub const SEPARATOR: &str = "__";
+
+/// Deserializes *T* from the variables of the current process starting with *prefix*
+///
+/// # Example
+/// ```
+/// use std::collections::HashMap;
+/// use lib_contra::deserialize::env::EnvDeserializer;
+///
+/// let vars = vec![
+///     ("GAME_GRAPHICS__WIDTH".to_string(), "1920".to_string()),
+///     ("GAME_GRAPHICS__HEIGHT".to_string(), "1080".to_string()),
+///     ("PATH".to_string(), "/usr/bin".to_string()),
+/// ];
+/// let de = EnvDeserializer::from_vars("GAME_", vars);
+///
+/// let config: HashMap<String, HashMap<String, u32>> = de.deserialize_into().unwrap();
+/// assert_eq!(config["graphics"]["width"], 1920);
+/// ```
+pub fn from_env<T: DeserializeOwned>(prefix: &str) -> Result<T, Error> {
+    EnvDeserializer::new(prefix).deserialize_into()
+}
+
+/// The variables below a common prefix, nested by their field paths
+#[derive(Debug, Clone)]
+enum Node {
+    Leaf(String),
+    Table(BTreeMap<String, Node>),
+}
+
+/// Maps environment variables onto any [Deserialize]
+///
+/// Numbers and booleans (`true`, `false`, `1`, `0`) are parsed from the variable,
+/// sequences are comma separated (`GAME_KEYS=w,a,s,d`) or given by index (`GAME_KEYS__0=w`).
+/// If a variable names a field that also has nested variables, the nested ones take precedence.
+///
+/// Also a [Source] of [Layered](crate::layered::Layered) configurations, overriding the values of earlier layers.
+#[derive(Debug, Clone)]
+pub struct EnvDeserializer {
+    node: Node,
+    /// Name of the variable, or the prefix of the nested variables
+    name: String,
+    /// Prefix of the names of the nested variables
+    nested: String,
+    /// A variable starting with the prefix whose name or value is not UTF-8, reported when deserializing
+    not_unicode: Option<String>,
+}
+
+impl EnvDeserializer {
+    /// Captures the variables of the current process starting with *prefix*
+    ///
+    /// Variables that are not UTF-8 are ignored, unless they start with *prefix*,
+    /// which fails the deserialization.
+    pub fn new(prefix: &str) -> Self {
+        let mut not_unicode = None;
+        let vars = std::env::vars_os().filter_map(|(name, value)| {
+            match (name.into_string(), value.into_string()) {
+                (Ok(name), Ok(value)) => Some((name, value)),
+                (name, _) => {
+                    let name = match name {
+                        Ok(name) => name,
+                        Err(name) => name.to_string_lossy().into_owned(),
+                    };
+                    if name.starts_with(prefix) && not_unicode.is_none() {
+                        not_unicode = Some(name);
+                    }
+                    None
+                }
+            }
+        });
+        let vars: Vec<_> = vars.collect();
+        Self {
+            not_unicode,
+            ..Self::from_vars(prefix, vars)
+        }
+    }
+
+    /// Captures the given variables starting with *prefix*, all others are ignored
+    pub fn from_vars<I: IntoIterator<Item = (String, String)>>(prefix: &str, vars: I) -> Self {
+        let mut root = BTreeMap::new();
+        for (name, value) in vars {
+            let path = match name.strip_prefix(prefix) {
+                Some(path) if !path.is_empty() => path.to_lowercase(),
+                _ => continue,
+            };
+            let path: Vec<&str> = path.split(SEPARATOR).collect();
+            insert(&mut root, &path, value);
+        }
+        Self {
+            node: Node::Table(root),
+            name: prefix.to_string(),
+            nested: prefix.to_string(),
+            not_unicode: None,
+        }
+    }
+
+    /// Deserializes *T* from the captured variables
+    pub fn deserialize_into<T: DeserializeOwned>(self) -> Result<T, Error> {
+        T::deserialize(self)
+    }
+
+    /// A single variable, e.g. a string of the environment layer of a merged configuration
+    pub(crate) fn text(name: &str, value: String) -> Self {
+        Self {
+            node: Node::Leaf(value),
+            name: name.to_string(),
+            nested: format!("{}{}", name, SEPARATOR),
+            not_unicode: None,
+        }
+    }
+
+    fn child(&self, key: &str, node: Node) -> Self {
+        let name = format!("{}{}", self.nested, key.to_uppercase());
+        Self {
+            node,
+            nested: format!("{}{}", name, SEPARATOR),
+            name,
+            not_unicode: None,
+        }
+    }
+
+    fn check_unicode(&self) -> Result<(), Error> {
+        match &self.not_unicode {
+            Some(name) => Err(Error::invalid_value(
+                format!("`{}`", name),
+                "a variable in UTF-8",
+            )),
+            None => Ok(()),
+        }
+    }
+
+    fn leaf(&self, expected: &str) -> Result<&str, Error> {
+        match &self.node {
+            Node::Leaf(value) => Ok(value.trim()),
+            Node::Table(_) => Err(Error::invalid_type("map", expected)),
+        }
+    }
+
+    fn parse<T: FromStr>(&self) -> Result<T, Error> {
+        let value = self.leaf(type_name::<T>())?;
+        value
+            .parse()
+            .map_err(|_| self.invalid_value(value, type_name::<T>()))
+    }
+
+    fn invalid_value(&self, value: &str, expected: &str) -> Error {
+        Error::invalid_value(format!("`{}` in `{}`", value, self.name), expected)
+    }
+}
+
+fn insert(table: &mut BTreeMap<String, Node>, path: &[&str], value: String) {
+    match path {
+        [] => {}
+        // nested variables take precedence over a plain one
+        [key] => {
+            table.entry(key.to_string()).or_insert(Node::Leaf(value));
+        }
+        [key, rest @ ..] => {
+            let node = table
+                .entry(key.to_string())
+                .or_insert_with(|| Node::Table(BTreeMap::new()));
+            if let Node::Leaf(_) = node {
+                *node = Node::Table(BTreeMap::new());
+            }
+            if let Node::Table(table) = node {
+                insert(table, rest, value);
+            }
+        }
+    }
+}
+
+/// The elements of a comma separated list, an empty string is an empty list
+pub(crate) fn split_list(list: &str) -> Vec<String> {
+    match list.trim() {
+        "" => vec![],
+        list => list
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .collect(),
+    }
+}
+
+macro_rules! impl_deserialize_parsed {
+    ($ttype: ident, $deserialize_fn: ident, $visit_fn: ident) => {
+        fn $deserialize_fn<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+            v.$visit_fn(self.parse::<$ttype>()?)
+        }
+    };
+}
+
+impl<'de> Deserializer<'de> for EnvDeserializer {
+    fn deserialize_map<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        self.check_unicode()?;
+        match self.node {
+            Node::Table(table) => v.visit_map(EnvMap {
+                parent: EnvDeserializer {
+                    node: Node::Table(BTreeMap::new()),
+                    name: self.name,
+                    nested: self.nested,
+                    not_unicode: None,
+                },
+                iter: table.into_iter(),
+                value: None,
+            }),
+            Node::Leaf(value) => Err(Error::invalid_type(&format!("`{}`", value), "map")),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        self.check_unicode()?;
+        let items: Vec<EnvDeserializer> = match &self.node {
+            Node::Leaf(list) => split_list(list)
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| self.child(&i.to_string(), Node::Leaf(item)))
+                .collect(),
+            Node::Table(table) => {
+                let mut items = table
+                    .iter()
+                    .map(|(key, node)| match key.parse::<usize>() {
+                        Ok(i) => Ok((i, self.child(key, node.clone()))),
+                        Err(_) => Err(Error::invalid_value(
+                            format!("`{}{}`", self.nested, key.to_uppercase()),
+                            "an index",
+                        )),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                items.sort_by_key(|(i, _)| *i);
+                items.into_iter().map(|(_, item)| item).collect()
+            }
+        };
+        v.visit_seq(EnvSeq {
+            iter: items.into_iter(),
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        self.deserialize_map(v)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        match &self.node {
+            // not trimmed, the whitespace may be intended
+            Node::Leaf(value) => v.visit_str(value),
+            Node::Table(_) => Err(Error::invalid_type("map", "str")),
+        }
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        match self.node {
+            Node::Leaf(_) => self.deserialize_str(v),
+            Node::Table(_) => self.deserialize_map(v),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        let value = self.leaf("bool")?;
+        match parse_bool(value) {
+            Some(bool) => v.visit_bool(bool),
+            None => Err(self.invalid_value(value, "bool")),
+        }
+    }
+
+    impl_deserialize_parsed!(i8, deserialize_i8, visit_i8);
+    impl_deserialize_parsed!(i16, deserialize_i16, visit_i16);
+    impl_deserialize_parsed!(i32, deserialize_i32, visit_i32);
+    impl_deserialize_parsed!(i64, deserialize_i64, visit_i64);
+    impl_deserialize_parsed!(i128, deserialize_i128, visit_i128);
+    impl_deserialize_parsed!(isize, deserialize_isize, visit_isize);
+    impl_deserialize_parsed!(u8, deserialize_u8, visit_u8);
+    impl_deserialize_parsed!(u16, deserialize_u16, visit_u16);
+    impl_deserialize_parsed!(u32, deserialize_u32, visit_u32);
+    impl_deserialize_parsed!(u64, deserialize_u64, visit_u64);
+    impl_deserialize_parsed!(u128, deserialize_u128, visit_u128);
+    impl_deserialize_parsed!(usize, deserialize_usize, visit_usize);
+    impl_deserialize_parsed!(f32, deserialize_f32, visit_f32);
+    impl_deserialize_parsed!(f64, deserialize_f64, visit_f64);
+}
+
+struct EnvMap {
+    /// Names the children of the map
+    parent: EnvDeserializer,
+    iter: btree_map::IntoIter<String, Node>,
+    value: Option<EnvDeserializer>,
+}
+
+impl<'de> MapAccess<'de> for EnvMap {
+    fn next_value<V: Deserialize<'de>>(&mut self) -> Result<V, Error> {
+        match self.value.take() {
+            Some(value) => V::deserialize(value),
+            None => Err(Error::custom("requested a map value before its key")),
+        }
+    }
+
+    fn next_key<K: Deserialize<'de>>(&mut self) -> Result<Option<K>, Error> {
+        match self.iter.next() {
+            Some((key, node)) => {
+                self.value = Some(self.parent.child(&key, node));
+                K::deserialize(self.parent.child(&key, Node::Leaf(key.clone()))).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+struct EnvSeq {
+    iter: vec::IntoIter<EnvDeserializer>,
+}
+
+impl<'de> SeqAccess<'de> for EnvSeq {
+    fn next_value<V: Deserialize<'de>>(&mut self) -> Result<Option<V>, Error> {
+        self.iter.next().map(V::deserialize).transpose()
+    }
+}
+
+// ##########################################
+
+impl Source for EnvDeserializer {
+    fn name(&self) -> String {
+        "environment".to_string()
+    }
+
+    fn is_textual(&self) -> bool {
+        true
+    }
+
+    /// Values stay strings, which [Layered](crate::layered::Layered) parses just like the variables,
+    /// tables whose keys are all indices become arrays
+    fn load(&self) -> Result<Option<Value>, Error> {
+        self.check_unicode()?;
+        Ok(Some(to_value(&self.node)))
+    }
+}
+
+fn to_value(node: &Node) -> Value {
+    match node {
+        Node::Leaf(value) => Value::String(value.clone()),
+        Node::Table(table)
+            if !table.is_empty() && table.keys().all(|key| key.parse::<usize>().is_ok()) =>
+        {
+            let mut items: Vec<(usize, Value)> = table
+                .iter()
+                .map(|(key, node)| (key.parse().unwrap_or_default(), to_value(node)))
+                .collect();
+            items.sort_by_key(|(i, _)| *i);
+            Value::Array(items.into_iter().map(|(_, item)| item).collect())
+        }
+        Node::Table(table) => Value::Object(
+            table
+                .iter()
+                .map(|(key, node)| (key.clone(), to_value(node)))
+                .collect(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{json, layered::Layered};
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn nested_paths() {
+        let de = EnvDeserializer::from_vars(
+            "GAME_",
+            vars(&[
+                ("GAME_GRAPHICS__WIDTH", "1920"),
+                ("GAME_GRAPHICS__MAX_FPS", "144"),
+                ("GAME_AUDIO__MUTED", "TRUE"),
+                ("OTHER_AUDIO__MUTED", "x"),
+            ]),
+        );
+
+        let config: HashMap<String, Value> = de.clone().deserialize_into().unwrap();
+        assert_eq!(config.len(), 2);
+        assert_eq!(config["graphics"]["max_fps"].as_u64(), Some(144));
+
+        let graphics: HashMap<String, HashMap<String, u32>> = EnvDeserializer::from_vars(
+            "GAME_",
+            vars(&[
+                ("GAME_GRAPHICS__WIDTH", "1920"),
+                ("GAME_GRAPHICS__MAX_FPS", "144"),
+            ]),
+        )
+        .deserialize_into()
+        .unwrap();
+        assert_eq!(graphics["graphics"]["width"], 1920);
+        assert_eq!(graphics["graphics"]["max_fps"], 144);
+    }
+
+    #[test]
+    fn lists_are_comma_separated_or_indexed() {
+        let de = EnvDeserializer::from_vars(
+            "GAME_",
+            vars(&[
+                ("GAME_KEYS", "w, a,s"),
+                ("GAME_EMPTY", ""),
+                ("GAME_SLOTS__1", "2"),
+                ("GAME_SLOTS__0", "1"),
+            ]),
+        );
+        let lists: HashMap<String, Vec<String>> = de.deserialize_into().unwrap();
+        assert_eq!(lists["keys"], vec!["w", "a", "s"]);
+        assert!(lists["empty"].is_empty());
+        assert_eq!(lists["slots"], vec!["1", "2"]);
+    }
+
+    #[test]
+    fn parse_errors_name_the_variable() {
+        let de = EnvDeserializer::from_vars("GAME_", vars(&[("GAME_AUDIO__VOLUME", "loud")]));
+        let err = de
+            .deserialize_into::<HashMap<String, HashMap<String, u8>>>()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid value: found `loud` in `GAME_AUDIO__VOLUME`, expected u8"
+        );
+    }
+
+    #[test]
+    fn overrides_earlier_layers() {
+        let env = EnvDeserializer::from_vars(
+            "GAME_",
+            vars(&[
+                ("GAME_AUDIO__VOLUME", "7"),
+                ("GAME_KEYS__0", "up"),
+                ("GAME_MODS", "a, b"),
+            ]),
+        );
+        let (merged, origins) = Layered::new()
+            .source((
+                "defaults",
+                json!({ "audio": { "volume": 5, "muted": false }, "keys": ["w"] }),
+            ))
+            .source(env)
+            .merge()
+            .unwrap();
+
+        assert_eq!(merged["audio"]["volume"].as_u64(), Some(7));
+        assert_eq!(merged["keys"], json!(["up"]));
+        assert_eq!(merged["mods"], json!("a, b"));
+        assert_eq!(origins.get("/audio/volume"), Some("environment"));
+        assert_eq!(origins.get("/audio/muted"), Some("defaults"));
+    }
+
+    /// Runs in a child process, setting variables would race with the other tests
+    #[cfg(unix)]
+    #[test]
+    fn variables_that_are_not_unicode() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt, process::Command};
+
+        if std::env::var_os("CONTRA_TEST_CHILD").is_some() {
+            let config: HashMap<String, String> = from_env("CONTRA_TEST_VALID_").unwrap();
+            assert_eq!(config["name"], "hero");
+
+            let err = from_env::<HashMap<String, String>>("CONTRA_TEST_INVALID_").unwrap_err();
+            assert!(matches!(err, Error::InvalidValue { .. }));
+            assert!(EnvDeserializer::new("CONTRA_TEST_INVALID_").load().is_err());
+            return;
+        }
+
+        let not_unicode = OsStr::from_bytes(b"\xff\xfe");
+        let output = Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "deserialize::env::test::variables_that_are_not_unicode",
+            ])
+            .env("CONTRA_TEST_CHILD", "1")
+            .env("CONTRA_UNRELATED", not_unicode)
+            .env("CONTRA_TEST_VALID_NAME", "hero")
+            .env("CONTRA_TEST_INVALID_NAME", "hero")
+            .env("CONTRA_TEST_INVALID_TITLE", not_unicode)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert!(String::from_utf8_lossy(&output.stdout).contains("1 passed"));
+    }
+}
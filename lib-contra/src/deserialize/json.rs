@@ -1,169 +1,613 @@
-use std::{
-    io::Cursor,
-    str::{from_utf8, FromStr},
+use std::{any::type_name, borrow::Cow, fmt::Write, io, ops::Range, str::FromStr};
+
+use crate::{
+    error::Error,
+    value::pointer::{self, Pointer},
 };
 
-use crate::error::AnyError;
+use super::{
+    Deserialize, DeserializeOwned, Deserializer, IoRead, MapAccess, Peek, Reference, SeqAccess,
+    SliceRead, Visitor,
+};
 
-use super::{Deserialize, Deserializer, MapAccess, Peek, SeqAccess, Visitor};
+/// Deserializes JSON held in memory
+///
+/// Strings without escape sequences can be borrowed from *str*, e.g. as `&'de str` or `Cow<'de, str>`.
+///
+/// # Example
+/// ```
+/// use std::borrow::Cow;
+/// use lib_contra::deserialize::json::FromJson;
+///
+/// let input = String::from("[\"plain\", \"esc\\naped\"]");
+/// let names: Vec<Cow<str>> = FromJson::from_json(&input).unwrap();
+/// assert!(matches!(names[0], Cow::Borrowed("plain")));
+/// assert!(matches!(names[1], Cow::Owned(_)));
+/// ```
+pub trait FromJson<'de>: Sized {
+    fn from_json(str: &'de str) -> Result<Self, Error>;
 
-pub trait FromJson: Sized {
-    fn from_json(str: &str) -> Result<Self, AnyError>;
+    /// Deserializes in collect mode, returning every missing, unknown, duplicate and mistyped field at once
+    fn from_json_collect(str: &'de str) -> Result<Self, Vec<Error>>;
 }
 
-impl<D: Deserialize> FromJson for D {
-    fn from_json(str: &str) -> Result<Self, AnyError> {
-        let mut de = JsonDeserializer {
-            read: Cursor::new(str),
-        };
+impl<'de, D: Deserialize<'de>> FromJson<'de> for D {
+    fn from_json(str: &'de str) -> Result<Self, Error> {
+        let mut de = JsonDeserializer::new(SliceRead::new(str.as_bytes()));
         Self::deserialize(&mut de)
     }
+
+    fn from_json_collect(str: &'de str) -> Result<Self, Vec<Error>> {
+        let mut de = JsonDeserializer::collecting(SliceRead::new(str.as_bytes()));
+        let result = Self::deserialize(&mut de);
+        let mut errors = de.take_errors();
+        match result {
+            Ok(val) if errors.is_empty() => Ok(val),
+            Ok(_) => Err(errors),
+            Err(err) if errors.is_empty() => Err(vec![err]),
+            Err(err) => {
+                if !is_recoverable(&err) {
+                    errors.push(err);
+                }
+                Err(errors)
+            }
+        }
+    }
+}
+
+/// Deserializes JSON from any [io::Read], without requiring [io::Seek]
+///
+/// # Example
+/// ```
+/// use lib_contra::deserialize::json::from_reader;
+///
+/// let stdin_like: &[u8] = b"[\"1\", \"2\", \"3\"]";
+/// let values: Vec<u32> = from_reader(stdin_like).unwrap();
+/// assert_eq!(values, vec![1, 2, 3]);
+/// ```
+pub fn from_reader<D: DeserializeOwned, R: io::Read>(read: R) -> Result<D, Error> {
+    let mut de = JsonDeserializer::new(IoRead::new(read));
+    D::deserialize(&mut de)
 }
 
 macro_rules! impl_deserializer_primitive {
     ($ttype: ident, $deserialize_fn: ident, $parse_fn: ident, $visit_fn: ident) => {
-        fn $deserialize_fn<V: Visitor>(self, v: V) -> Result<V::Value, AnyError> {
+        fn $deserialize_fn<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
             self.parse_whitespaces()?;
-            match self.read.peek()? {
+            let start = self.location();
+            let val = match self.peek()? {
                 Some(b'-') | Some(b'0') | Some(b'1') | Some(b'2') | Some(b'3') | Some(b'4')
                 | Some(b'5') | Some(b'6') | Some(b'7') | Some(b'8') | Some(b'9') | Some(b'.') => {
-                    let str = self
-                        .read
-                        .read_until(&[b' ', b',', b'\t', b'\n', b']', b'}', b':'])?;
-                    let str = from_utf8(str.as_slice())?;
-                    let val = self.$parse_fn(str)?;
-                    v.$visit_fn(val)
+                    self.read_literal().and_then(|str| self.$parse_fn(&str))
                 }
+                Some(b'"') => self.read_string().and_then(|str| self.$parse_fn(&str)),
+                other => Err(unexpected(other, stringify!($ttype))),
+            };
+            val.and_then(|val| v.$visit_fn(val))
+                .map_err(|err| self.locate(err, start))
+        }
+    };
+}
+
+/// Builds the error for a value that did not start as expected
+fn unexpected(found: Option<u8>, expected: &str) -> Error {
+    match found {
+        Some(b'{') => Error::invalid_type("map", expected),
+        Some(b'[') => Error::invalid_type("seq", expected),
+        Some(b'"') => Error::invalid_type("str", expected),
+        Some(b't') | Some(b'f') => Error::invalid_type("bool", expected),
+        Some(b'n') => Error::invalid_type("null", expected),
+        Some(char) => Error::invalid_type(&format!("`{}`", char as char), expected),
+        None => Error::syntax(format!("unexpected end of input, expected a {}", expected)),
+    }
+}
+
+/// Whether deserialization can carry on past the error in collect mode
+fn is_recoverable(err: &Error) -> bool {
+    !matches!(err.kind(), Error::Io(_) | Error::Syntax(_))
+}
+
+/// A single step of the logical path to the value currently being deserialized
+enum Segment {
+    /// Range of the key within the shared key buffer
+    Key(Range<usize>),
+    Index(usize),
+}
+
+/// Deserializes JSON from any [Peek]
+///
+/// Keeps track of the current line and column as well as of the logical path (e.g. `player.items[3].slot`)
+/// so that any error can be reported with its location in the input.
+///
+/// Created via [collecting](JsonDeserializer::collecting) it keeps going after recoverable errors,
+/// see [take_errors](JsonDeserializer::take_errors).
+pub struct JsonDeserializer<P> {
+    read: P,
+    line: usize,
+    column: usize,
+    depth: usize,
+    path: Vec<Segment>,
+    /// Backing storage of all keys in *path*, avoids an allocation per key
+    keys: String,
+    reading_key: bool,
+    key: Option<Range<usize>>,
+    errors: Option<Vec<Error>>,
+}
+
+struct JsonMap<'a, P> {
+    de: &'a mut JsonDeserializer<P>,
+    has_key: bool,
+}
+
+struct JsonArray<'a, P> {
+    de: &'a mut JsonDeserializer<P>,
+    index: usize,
+}
+
+impl<P> JsonDeserializer<P> {
+    pub fn new(peek: P) -> Self {
+        Self {
+            read: peek,
+            line: 1,
+            column: 1,
+            depth: 0,
+            path: vec![],
+            keys: String::new(),
+            reading_key: false,
+            key: None,
+            errors: None,
+        }
+    }
+
+    /// Creates a deserializer in collect mode
+    ///
+    /// Missing, unknown, duplicate and mistyped fields are recorded instead of aborting deserialization.
+    pub fn collecting(peek: P) -> Self {
+        Self {
+            errors: Some(vec![]),
+            ..Self::new(peek)
+        }
+    }
+
+    /// Removes and returns the errors recorded in collect mode
+    pub fn take_errors(&mut self) -> Vec<Error> {
+        self.errors.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    fn error_count(&self) -> usize {
+        self.errors.as_ref().map_or(0, Vec::len)
+    }
+
+    /// Records the error in collect mode, hands it back otherwise
+    fn record(&mut self, err: Error) -> Result<(), Error> {
+        match self.errors.as_mut() {
+            Some(errors) if is_recoverable(&err) => {
+                errors.push(err);
+                Ok(())
+            }
+            _ => Err(err),
+        }
+    }
+
+    /// Current (line, column) in the input, both 1-based
+    pub fn location(&self) -> (usize, usize) {
+        (self.line, self.column)
+    }
+
+    /// Logical path of the value currently being deserialized
+    pub fn path(&self) -> String {
+        let mut path = String::new();
+        for segment in self.path.iter() {
+            match segment {
+                Segment::Key(key) if path.is_empty() => path.push_str(&self.keys[key.clone()]),
+                Segment::Key(key) => write!(path, ".{}", &self.keys[key.clone()]).unwrap(),
+                Segment::Index(i) => write!(path, "[{}]", i).unwrap(),
+            }
+        }
+        path
+    }
+
+    fn push_key(&mut self, key: Range<usize>) {
+        self.path.push(Segment::Key(key));
+    }
+
+    fn pop_segment(&mut self) {
+        if let Some(Segment::Key(key)) = self.path.pop() {
+            self.keys.truncate(key.start);
+        }
+    }
+
+    fn truncate_path(&mut self, len: usize) {
+        while self.path.len() > len {
+            self.pop_segment();
+        }
+    }
+
+    fn locate(&self, err: Error, (line, column): (usize, usize)) -> Error {
+        err.at(line, column, self.path())
+    }
+
+    fn error_here(&self, err: Error) -> Error {
+        self.locate(err, self.location())
+    }
+
+    fn advance(&mut self, char: u8) {
+        if char == b'\n' {
+            self.line += 1;
+            self.column = 1;
+        } else if char & 0xC0 != 0x80 {
+            // only count the first byte of multi-byte utf8 characters
+            self.column += 1;
+        }
+    }
+
+    fn advance_over(&mut self, bytes: &[u8]) {
+        let chars = |bytes: &[u8]| bytes.iter().filter(|char| *char & 0xC0 != 0x80).count();
+        match bytes.iter().rposition(|char| *char == b'\n') {
+            Some(last) => {
+                self.line += bytes.iter().filter(|char| **char == b'\n').count();
+                self.column = 1 + chars(&bytes[last + 1..]);
+            }
+            None => self.column += chars(bytes),
+        }
+    }
+
+    fn parse_signed_number<I: FromStr>(&mut self, str: &str) -> Result<I, Error> {
+        str.parse()
+            .map_err(|_| Error::invalid_value(format!("`{}`", str), type_name::<I>()))
+    }
+
+    fn parse_unsigned_number<U: FromStr>(&mut self, str: &str) -> Result<U, Error> {
+        str.parse()
+            .map_err(|_| Error::invalid_value(format!("`{}`", str), type_name::<U>()))
+    }
+
+    fn parse_floating_number<F: FromStr>(&mut self, str: &str) -> Result<F, Error> {
+        str.parse()
+            .map_err(|_| Error::invalid_value(format!("`{}`", str), type_name::<F>()))
+    }
+
+    fn parse_bool(&mut self, str: &str) -> Result<bool, Error> {
+        match str {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(Error::invalid_value(format!("`{}`", str), "bool")),
+        }
+    }
+}
+
+impl<'de, P: Peek<'de>> JsonDeserializer<P> {
+    /// Deserializes only the value addressed by the JSON *pointer*, e.g. `/graphics/resolution/0`
+    ///
+    /// Everything in front of the value is skipped without being deserialized, nothing after it is read.
+    /// Returns *None* if the input does not contain the addressed value.
+    ///
+    /// # Example
+    /// ```
+    /// use lib_contra::deserialize::{json::JsonDeserializer, SliceRead};
+    ///
+    /// let input = "{ \"audio\": { \"volume\": 3 }, \"graphics\": { \"resolution\": [1920, 1080] } }";
+    /// let mut de = JsonDeserializer::new(SliceRead::new(input.as_bytes()));
+    /// let height: Option<u32> = de.deserialize_at("/graphics/resolution/1").unwrap();
+    /// assert_eq!(height, Some(1080));
+    /// ```
+    pub fn deserialize_at<T: Deserialize<'de>>(
+        &mut self,
+        pointer: &str,
+    ) -> Result<Option<T>, Error> {
+        let pointer = Pointer::parse(pointer)?;
+        let (len, depth) = (self.path.len(), self.depth);
+        let val = match self.seek(&pointer) {
+            Ok(true) => T::deserialize(&mut *self).map(Some),
+            Ok(false) => Ok(None),
+            Err(err) => Err(err),
+        };
+        self.truncate_path(len);
+        self.depth = depth;
+        val
+    }
+
+    /// Moves in front of the value addressed by *pointer*, returns whether it exists
+    fn seek(&mut self, pointer: &Pointer) -> Result<bool, Error> {
+        for token in pointer.tokens() {
+            self.parse_whitespaces()?;
+            let found = match self.peek()? {
+                Some(b'{') => self.seek_key(token)?,
+                Some(b'[') => match pointer::index(token) {
+                    Some(index) => self.seek_index(index)?,
+                    None => false,
+                },
+                _ => false,
+            };
+            if !found {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn seek_key(&mut self, token: &str) -> Result<bool, Error> {
+        self.consume()?;
+        self.depth += 1;
+        let depth = self.depth;
+        loop {
+            self.parse_whitespaces()?;
+            match self.peek()? {
                 Some(b'"') => {
-                    self.read.consume()?;
-                    let str = self.read.read_until(&[b'"'])?;
-                    self.read.consume()?;
-                    let str = from_utf8(str.as_slice())?;
-                    let val = self.$parse_fn(str)?;
-                    v.$visit_fn(val)
+                    self.reading_key = true;
+                    let key = self.read_string();
+                    self.reading_key = false;
+                    let matches = key? == token;
+                    let key = self.key.take();
+                    self.parse_whitespaces()?;
+                    if self.peek()? != Some(b':') {
+                        return Err(self.error_here(Error::syntax("expected a map assignment")));
+                    }
+                    self.consume()?;
+                    match (matches, key) {
+                        (true, Some(key)) => {
+                            self.push_key(key);
+                            return Ok(true);
+                        }
+                        (_, key) => {
+                            if let Some(key) = key {
+                                self.keys.truncate(key.start);
+                            }
+                            self.recover(depth)?;
+                        }
+                    }
+                }
+                Some(b',') => self.consume()?,
+                Some(b'}') => return Ok(false),
+                _ => return Err(self.error_here(Error::syntax("expected a map key"))),
+            }
+        }
+    }
+
+    fn seek_index(&mut self, index: usize) -> Result<bool, Error> {
+        self.consume()?;
+        self.depth += 1;
+        let depth = self.depth;
+        let mut current = 0;
+        loop {
+            self.parse_whitespaces()?;
+            match self.peek()? {
+                Some(b']') => return Ok(false),
+                Some(b',') => self.consume()?,
+                Some(_) if current == index => {
+                    self.path.push(Segment::Index(index));
+                    return Ok(true);
                 }
-                Some(_) | None => {
-                    Err(concat!("expected a ", stringify!($ttype), " to start").into())
+                Some(_) => {
+                    self.recover(depth)?;
+                    current += 1;
                 }
+                None => return Err(self.error_here(Error::syntax("expected a seq element"))),
             }
         }
-    };
-    ($ttype: ident as $cast: ident, $deserialize_fn: ident, $parse_fn: ident, $visit_fn: ident) => {
-        fn $deserialize_fn<V: Visitor>(self, v: V) -> Result<V::Value, AnyError> {
+    }
+
+    /// Skips input until the next `,`, `}` or `]` at the given nesting depth
+    fn recover(&mut self, depth: usize) -> Result<(), Error> {
+        loop {
             self.parse_whitespaces()?;
-            match self.read.peek()? {
-                Some(b'-') | Some(b'0') | Some(b'1') | Some(b'2') | Some(b'3') | Some(b'4')
-                | Some(b'5') | Some(b'6') | Some(b'7') | Some(b'8') | Some(b'9') | Some(b'.') => {
-                    let str = self
-                        .read
-                        .read_until(&[b' ', b',', b'\t', b'\n', b']', b'}', b':'])?;
-                    let str = from_utf8(str.as_slice())?;
-                    let val = self.$parse_fn(str)?;
-                    v.$visit_fn(val as $cast)
+            match self.peek()? {
+                Some(b',') | Some(b'}') | Some(b']') if self.depth == depth => return Ok(()),
+                Some(b'{') | Some(b'[') => {
+                    self.consume()?;
+                    self.depth += 1;
                 }
-                Some(b'"') => {
-                    self.read.consume()?;
-                    let str = self.read.read_until(&[b'"'])?;
-                    self.read.consume()?;
-                    let str = from_utf8(str.as_slice())?;
-                    let val = self.$parse_fn(str)?;
-                    v.$visit_fn(val)
+                Some(b'}') | Some(b']') => {
+                    self.consume()?;
+                    self.depth -= 1;
                 }
-                Some(_) | None => {
-                    Err(concat!("expected a ", stringify!($ttype), " to start").into())
+                Some(b'"') => {
+                    self.read_string()?;
                 }
+                Some(_) => self.consume()?,
+                None => return Err(self.error_here(Error::syntax("unexpected end of input"))),
             }
         }
-    };
-}
+    }
 
-pub struct JsonDeserializer<P: Peek> {
-    read: P,
-}
+    fn peek(&mut self) -> Result<Option<u8>, Error> {
+        self.read.peek()
+    }
 
-struct JsonMap<'de, P: Peek> {
-    de: &'de mut JsonDeserializer<P>,
-}
+    fn consume(&mut self) -> Result<(), Error> {
+        if let Some(char) = self.read.peek()? {
+            self.read.consume()?;
+            self.advance(char);
+        }
+        Ok(())
+    }
 
-struct JsonArray<'de, P: Peek> {
-    de: &'de mut JsonDeserializer<P>,
-}
+    fn read_until(&mut self, end: &[u8]) -> Result<Vec<u8>, Error> {
+        let bytes = self.read.read_until(end)?;
+        self.advance_over(&bytes);
+        Ok(bytes)
+    }
 
-impl<P: Peek> JsonDeserializer<P> {
-    pub fn new(peek: P) -> Self {
-        Self { read: peek }
+    fn parse_whitespaces(&mut self) -> Result<(), Error> {
+        while let Some(b' ' | b'\n' | b'\t' | b'\r') = self.peek()? {
+            self.consume()?;
+        }
+        Ok(())
+    }
+
+    /// Reads an unquoted token such as a number, `true`, `false` or `null`
+    fn read_literal(&mut self) -> Result<String, Error> {
+        let str = self.read_until(b" ,\t\n\r]}:")?;
+        String::from_utf8(str).map_err(|err| err.utf8_error().into())
     }
 
-    fn parse_whitespaces(&mut self) -> Result<(), AnyError> {
-        self.read.consume_matching(&[b' ', b'\n', b'\t'])
+    fn read_until_borrowed(&mut self, end: &[u8]) -> Result<Reference<'de>, Error> {
+        let bytes = self.read.read_until_borrowed(end)?;
+        self.advance_over(&bytes);
+        Ok(bytes)
     }
 
-    fn parse_signed_number<I: FromStr>(&mut self, str: &str) -> Result<I, AnyError>
-    where
-        <I as FromStr>::Err: std::error::Error + 'static,
-    {
-        str.parse().map_err(|err| Box::from(err))
+    /// Reads a quoted string, remembering it if it is a map key
+    ///
+    /// The string is borrowed from the input unless it contains escape sequences or the input is not in memory.
+    fn read_string(&mut self) -> Result<Cow<'de, str>, Error> {
+        self.consume()?;
+        let str = match self.read_until_borrowed(b"\"\\")? {
+            Reference::Borrowed(bytes) if self.peek()? == Some(b'"') => {
+                Cow::Borrowed(std::str::from_utf8(bytes)?)
+            }
+            bytes => {
+                let mut bytes = Vec::from(bytes);
+                loop {
+                    match self.peek()? {
+                        Some(b'"') => break,
+                        Some(_) => {
+                            self.read_escape(&mut bytes)?;
+                            bytes.extend(self.read_until(b"\"\\")?);
+                        }
+                        None => return Err(Error::syntax("unterminated string")),
+                    }
+                }
+                Cow::Owned(String::from_utf8(bytes).map_err(|err| Error::from(err.utf8_error()))?)
+            }
+        };
+        self.consume()?;
+        if self.reading_key {
+            let start = self.keys.len();
+            self.keys.push_str(&str);
+            self.key = Some(start..self.keys.len());
+        }
+        Ok(str)
     }
 
-    fn parse_unsigned_number<U: FromStr>(&mut self, str: &str) -> Result<U, AnyError>
-    where
-        <U as FromStr>::Err: std::error::Error + 'static,
-    {
-        str.parse().map_err(|err| Box::from(err))
+    /// Reads an escape sequence starting at the backslash and appends the character it stands for
+    fn read_escape(&mut self, bytes: &mut Vec<u8>) -> Result<(), Error> {
+        self.consume()?;
+        let escaped = self.peek()?;
+        self.consume()?;
+        let char = match escaped {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                let high = self.read_hex()?;
+                let code = match high {
+                    0xD800..=0xDBFF => {
+                        if self.peek()? != Some(b'\\') {
+                            return Err(Error::syntax("unpaired surrogate in escape sequence"));
+                        }
+                        self.consume()?;
+                        if self.peek()? != Some(b'u') {
+                            return Err(Error::syntax("unpaired surrogate in escape sequence"));
+                        }
+                        self.consume()?;
+                        let low = self.read_hex()?;
+                        if !(0xDC00..=0xDFFF).contains(&low) {
+                            return Err(Error::syntax("unpaired surrogate in escape sequence"));
+                        }
+                        0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                    }
+                    code => code,
+                };
+                char::from_u32(code)
+                    .ok_or_else(|| Error::syntax("unpaired surrogate in escape sequence"))?
+            }
+            Some(other) => {
+                return Err(Error::syntax(format!(
+                    "invalid escape sequence `\\{}`",
+                    other as char
+                )))
+            }
+            None => return Err(Error::syntax("unterminated string")),
+        };
+        bytes.extend_from_slice(char.encode_utf8(&mut [0; 4]).as_bytes());
+        Ok(())
     }
 
-    fn parse_floating_number<F: FromStr>(&mut self, str: &str) -> Result<F, AnyError>
-    where
-        <F as FromStr>::Err: std::error::Error + 'static,
-    {
-        str.parse().map_err(|err| Box::from(err))
+    /// Reads the four hex digits of a `\\u` escape sequence
+    fn read_hex(&mut self) -> Result<u32, Error> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .peek()?
+                .and_then(|char| (char as char).to_digit(16))
+                .ok_or_else(|| Error::syntax("expected four hex digits in escape sequence"))?;
+            self.consume()?;
+            code = code * 16 + digit;
+        }
+        Ok(code)
     }
 }
 
-impl<P: Peek> Deserializer for &mut JsonDeserializer<P> {
-    fn deserialize_map<V: Visitor>(self, v: V) -> Result<V::Value, AnyError> {
+impl<'de, P: Peek<'de>> Deserializer<'de> for &mut JsonDeserializer<P> {
+    fn deserialize_map<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
         self.parse_whitespaces()?;
-        match self.read.peek()? {
+        let start = self.location();
+        let depth = self.path.len();
+        let val = match self.peek()? {
             Some(b'{') => {
-                self.read.consume()?;
-                let val = v.visit_map(JsonMap { de: self });
-                self.read.consume()?;
-                val
-            }
-            Some(char) => {
-                Err(format!("expected a map to start but got \"{}\" instead", char).into())
+                self.consume()?;
+                self.depth += 1;
+                v.visit_map(JsonMap {
+                    de: self,
+                    has_key: false,
+                })
+                .and_then(|val| {
+                    self.parse_whitespaces()?;
+                    match self.peek()? {
+                        Some(b'}') => {
+                            self.consume()?;
+                            self.depth -= 1;
+                            Ok(val)
+                        }
+                        _ => Err(self.error_here(Error::syntax("expected the map to end"))),
+                    }
+                })
             }
-            None => Err("expected a map to start".into()),
-        }
+            other => Err(unexpected(other, "map")),
+        };
+        let val = val.map_err(|err| self.locate(err, start));
+        self.truncate_path(depth);
+        val
     }
 
-    fn deserialize_seq<V: Visitor>(self, v: V) -> Result<V::Value, AnyError> {
+    fn deserialize_seq<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
         self.parse_whitespaces()?;
-        match self.read.peek()? {
+        let start = self.location();
+        let depth = self.path.len();
+        let val = match self.peek()? {
             Some(b'[') => {
-                self.read.consume()?;
-                v.visit_seq(JsonArray { de: self })
+                self.consume()?;
+                self.depth += 1;
+                v.visit_seq(JsonArray { de: self, index: 0 })
             }
-            Some(_) | None => Err("expected a vec to start".into()),
-        }
+            other => Err(unexpected(other, "seq")),
+        };
+        let val = val.map_err(|err| self.locate(err, start));
+        self.truncate_path(depth);
+        val
     }
 
-    fn deserialize_struct<V: Visitor>(self, v: V) -> Result<V::Value, AnyError> {
+    fn deserialize_struct<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
         self.deserialize_map(v)
     }
 
-    fn deserialize_str<V: Visitor>(self, v: V) -> Result<V::Value, AnyError> {
+    fn deserialize_str<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
         self.parse_whitespaces()?;
-        match self.read.peek()? {
-            Some(b'"') => {
-                self.read.consume()?;
-                let str = self.read.read_until(&[b'"'])?;
-                self.read.consume()?;
-                let str = from_utf8(str.as_slice())?;
-                v.visit_str(str)
-            }
-            Some(_) | None => Err("expected a str to start".into()),
-        }
+        let start = self.location();
+        let val = match self.peek()? {
+            Some(b'"') => self.read_string().and_then(|str| match str {
+                Cow::Borrowed(str) => v.visit_borrowed_str(str),
+                Cow::Owned(str) => v.visit_str(&str),
+            }),
+            other => Err(unexpected(other, "str")),
+        };
+        val.map_err(|err| self.locate(err, start))
     }
 
     impl_deserializer_primitive!(i8, deserialize_i8, parse_signed_number, visit_i8);
@@ -180,54 +624,199 @@ impl<P: Peek> Deserializer for &mut JsonDeserializer<P> {
     impl_deserializer_primitive!(f64, deserialize_f64, parse_floating_number, visit_f64);
     impl_deserializer_primitive!(isize, deserialize_isize, parse_signed_number, visit_isize);
     impl_deserializer_primitive!(usize, deserialize_usize, parse_unsigned_number, visit_usize);
+
+    fn deserialize_bool<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        self.parse_whitespaces()?;
+        let start = self.location();
+        let val = match self.peek()? {
+            Some(b't') | Some(b'f') => self.read_literal().and_then(|str| self.parse_bool(&str)),
+            Some(b'"') => self.read_string().and_then(|str| self.parse_bool(&str)),
+            other => Err(unexpected(other, "bool")),
+        };
+        val.and_then(|val| v.visit_bool(val))
+            .map_err(|err| self.locate(err, start))
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        self.parse_whitespaces()?;
+        let start = self.location();
+        match self.peek()? {
+            Some(b'{') => self.deserialize_map(v),
+            Some(b'[') => self.deserialize_seq(v),
+            Some(b'"') => self.deserialize_str(v),
+            Some(_) => {
+                let val = self.read_literal().and_then(|str| match str.as_str() {
+                    "true" => v.visit_bool(true),
+                    "false" => v.visit_bool(false),
+                    "null" => v.visit_null(),
+                    number => visit_number(number, v),
+                });
+                val.map_err(|err| self.locate(err, start))
+            }
+            None => Err(self.error_here(unexpected(None, "value"))),
+        }
+    }
 }
 
-impl<'de, P: Peek> MapAccess for JsonMap<'de, P> {
-    fn next_value<V: Deserialize>(&mut self) -> Result<V, AnyError> {
+/// Visits an unquoted number with the smallest fitting type out of u64, i64, u128, i128 and f64
+fn visit_number<'de, V: Visitor<'de>>(number: &str, v: V) -> Result<V::Value, Error> {
+    if let Ok(number) = number.parse::<u64>() {
+        v.visit_u64(number)
+    } else if let Ok(number) = number.parse::<i64>() {
+        v.visit_i64(number)
+    } else if let Ok(number) = number.parse::<u128>() {
+        v.visit_u128(number)
+    } else if let Ok(number) = number.parse::<i128>() {
+        v.visit_i128(number)
+    } else {
+        match number.parse::<f64>() {
+            Ok(number) if number.is_finite() => v.visit_f64(number),
+            _ => Err(Error::invalid_value(format!("`{}`", number), "value")),
+        }
+    }
+}
+
+impl<'a, 'de, P: Peek<'de>> MapAccess<'de> for JsonMap<'a, P> {
+    fn next_value<V: Deserialize<'de>>(&mut self) -> Result<V, Error> {
         self.de.parse_whitespaces()?;
-        match self.de.read.peek()? {
+        match self.de.peek()? {
             Some(b':') => {
-                self.de.read.consume()?;
+                self.de.consume()?;
                 self.de.parse_whitespaces()?;
-                match self.de.read.peek()? {
+                match self.de.peek()? {
                     Some(b'0') | Some(b'1') | Some(b'2') | Some(b'3') | Some(b'4') | Some(b'5')
                     | Some(b'6') | Some(b'7') | Some(b'8') | Some(b'9') | Some(b'-')
-                    | Some(b'"') | Some(b'{') | Some(b'[') => Ok(V::deserialize(&mut *self.de)?),
-                    Some(_) | None => Err("expected a map value".into()),
+                    | Some(b'"') | Some(b'{') | Some(b'[') | Some(b't') | Some(b'f')
+                    | Some(b'n') => Ok(V::deserialize(&mut *self.de)?),
+                    Some(_) | None => {
+                        Err(self.de.error_here(Error::syntax("expected a map value")))
+                    }
                 }
             }
-            Some(_) | None => Err("expected a map assignment".into()),
+            Some(_) | None => Err(self
+                .de
+                .error_here(Error::syntax("expected a map assignment"))),
         }
     }
 
-    fn next_key<K: Deserialize>(&mut self) -> Result<Option<K>, AnyError> {
+    fn next_key<K: Deserialize<'de>>(&mut self) -> Result<Option<K>, Error> {
+        if self.has_key {
+            self.de.pop_segment();
+            self.has_key = false;
+        }
         self.de.parse_whitespaces()?;
-        match self.de.read.peek()? {
-            Some(b'"') => Ok(Some(K::deserialize(&mut *self.de)?)),
+        match self.de.peek()? {
+            Some(b'"') => {
+                let errors = self.de.error_count();
+                self.de.reading_key = true;
+                let key = K::deserialize(&mut *self.de);
+                self.de.reading_key = false;
+                let name = self.de.key.take();
+                match key {
+                    Ok(key) => {
+                        if let Some(name) = name {
+                            self.de.push_key(name);
+                            self.has_key = true;
+                        }
+                        Ok(Some(key))
+                    }
+                    Err(err) => {
+                        if let Some(name) = name {
+                            self.de.keys.truncate(name.start);
+                        }
+                        if self.de.error_count() == errors {
+                            self.de.record(err)?;
+                        }
+                        self.skip_value()?;
+                        self.next_key()
+                    }
+                }
+            }
             Some(b',') => {
-                self.de.read.consume()?;
+                self.de.consume()?;
                 self.next_key()
             }
             Some(b'}') => Ok(None),
-            Some(_) | None => Err("expected a map key".into()),
+            Some(_) | None => Err(self.de.error_here(Error::syntax("expected a map key"))),
+        }
+    }
+
+    fn report(&mut self, err: Error) -> Result<(), Error> {
+        let err = self.de.error_here(err);
+        self.de.record(err)
+    }
+
+    fn next_value_or_report<V: Deserialize<'de>>(&mut self) -> Result<Option<V>, Error> {
+        if self.de.errors.is_none() {
+            return self.next_value().map(Some);
+        }
+
+        let errors = self.de.error_count();
+        let depth = self.de.depth;
+        match self.next_value() {
+            Ok(val) => Ok(Some(val)),
+            Err(err) => {
+                // errors the value already reported itself are not recorded twice
+                if self.de.error_count() == errors {
+                    self.de.record(err)?;
+                }
+                self.de.recover(depth)?;
+                Ok(None)
+            }
         }
     }
 }
 
-impl<'de, P: Peek> SeqAccess for JsonArray<'de, P> {
-    fn next_value<V: Deserialize>(&mut self) -> Result<Option<V>, AnyError> {
+impl<'a, 'de, P: Peek<'de>> JsonMap<'a, P> {
+    /// Skips the assignment and value following a key
+    fn skip_value(&mut self) -> Result<(), Error> {
         self.de.parse_whitespaces()?;
-        match self.de.read.peek()? {
+        match self.de.peek()? {
+            Some(b':') => {
+                self.de.consume()?;
+                self.de.recover(self.de.depth)
+            }
+            _ => Err(self
+                .de
+                .error_here(Error::syntax("expected a map assignment"))),
+        }
+    }
+}
+
+impl<'a, 'de, P: Peek<'de>> SeqAccess<'de> for JsonArray<'a, P> {
+    fn next_value<V: Deserialize<'de>>(&mut self) -> Result<Option<V>, Error> {
+        self.de.parse_whitespaces()?;
+        match self.de.peek()? {
             Some(b',') => {
-                self.de.read.consume()?;
+                self.de.consume()?;
                 self.next_value()
             }
             Some(b']') => {
-                self.de.read.consume()?;
+                self.de.consume()?;
+                self.de.depth -= 1;
                 Ok(None)
             }
-            Some(b'"') | Some(_) => Ok(Some(V::deserialize(&mut *self.de)?)),
-            None => Err("expected a seq element".into()),
+            Some(b'"') | Some(_) => {
+                let errors = self.de.error_count();
+                let depth = self.de.depth;
+                self.de.path.push(Segment::Index(self.index));
+                let val = V::deserialize(&mut *self.de);
+                self.de.pop_segment();
+                self.index += 1;
+                match val {
+                    Ok(val) => Ok(Some(val)),
+                    Err(err) if self.de.errors.is_none() => Err(err),
+                    Err(err) => {
+                        // in collect mode the failed element is dropped, the result is discarded anyway
+                        if self.de.error_count() == errors {
+                            self.de.record(err)?;
+                        }
+                        self.de.recover(depth)?;
+                        self.next_value()
+                    }
+                }
+            }
+            None => Err(self.de.error_here(Error::syntax("expected a seq element"))),
         }
     }
 }
@@ -244,7 +833,7 @@ mod test {
 
         let input = "[32, 64]";
         let input = Cursor::new(input);
-        let mut de = JsonDeserializer { read: input };
+        let mut de = JsonDeserializer::new(input);
         let result = Vec::<i32>::deserialize(&mut de);
 
         dbg!(&result);
@@ -252,6 +841,18 @@ mod test {
         assert_eq!(result.unwrap(), expected);
     }
 
+    #[test]
+    fn parse_from_slice_test() {
+        let input = "{ \"2\": [32, 64],\n \"4\": [] }";
+        let mut de = JsonDeserializer::new(SliceRead::new(input.as_bytes()));
+        let map = HashMap::<i32, Vec<i32>>::deserialize(&mut de).unwrap();
+
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[&2], vec![32, 64]);
+        assert!(map[&4].is_empty());
+        assert_eq!(de.location(), (2, 11));
+    }
+
     #[test]
     fn parse_map_test() {
         let mut expected = HashMap::new();
@@ -259,7 +860,7 @@ mod test {
 
         let input = "{ \"2\": 32 }";
         let input = Cursor::new(input);
-        let mut de = JsonDeserializer { read: input };
+        let mut de = JsonDeserializer::new(input);
         let map = HashMap::<i32, i32>::deserialize(&mut de);
 
         dbg!(&map);
@@ -275,24 +876,24 @@ mod test {
             s: String,
         }
 
-        impl Deserialize for A {
-            fn deserialize<D: Deserializer>(des: D) -> Result<Self, AnyError> {
+        impl<'de> Deserialize<'de> for A {
+            fn deserialize<D: Deserializer<'de>>(des: D) -> Result<Self, Error> {
                 enum Field {
                     A,
                     S,
                 }
 
-                impl Deserialize for Field {
-                    fn deserialize<D: Deserializer>(des: D) -> Result<Self, AnyError> {
+                impl<'de> Deserialize<'de> for Field {
+                    fn deserialize<D: Deserializer<'de>>(des: D) -> Result<Self, Error> {
                         struct FieldVisitor {}
-                        impl Visitor for FieldVisitor {
+                        impl<'de> Visitor<'de> for FieldVisitor {
                             type Value = Field;
 
-                            fn visit_str(self, v: &str) -> Result<Self::Value, AnyError> {
+                            fn visit_str(self, v: &str) -> Result<Self::Value, Error> {
                                 match v {
                                     "a" => Ok(Field::A),
                                     "s" => Ok(Field::S),
-                                    val => Err(format!("unknown \"{}\" field for A", val).into()),
+                                    val => Err(Error::unknown_field(val, &["a", "s"])),
                                 }
                             }
 
@@ -306,14 +907,17 @@ mod test {
                 }
 
                 struct AVisitor {}
-                impl Visitor for AVisitor {
+                impl<'de> Visitor<'de> for AVisitor {
                     type Value = A;
 
                     fn expected_a(self) -> String {
                         "A".into()
                     }
 
-                    fn visit_map<M: MapAccess>(self, mut map: M) -> Result<Self::Value, AnyError> {
+                    fn visit_map<M: MapAccess<'de>>(
+                        self,
+                        mut map: M,
+                    ) -> Result<Self::Value, Error> {
                         let mut a = None;
                         let mut s = None;
 
@@ -321,23 +925,23 @@ mod test {
                             match key {
                                 Field::A => {
                                     if a.is_some() {
-                                        return Err("duplicate field a".into());
+                                        return Err(Error::duplicate_field("a"));
                                     };
                                     a = Some(map.next_value()?)
                                 }
                                 Field::S => {
                                     if s.is_some() {
-                                        return Err("duplicate field s".into());
+                                        return Err(Error::duplicate_field("s"));
                                     };
                                     s = Some(map.next_value()?)
                                 }
                             }
                         }
 
-                        let a = a.ok_or_else(|| "missing field a")?;
-                        let s = s.ok_or_else(|| "missing field s")?;
+                        let a = a.ok_or_else(|| Error::missing_field("a"))?;
+                        let s = s.ok_or_else(|| Error::missing_field("s"))?;
 
-                        Ok(A { a: a, s: s })
+                        Ok(A { a, s })
                     }
                 }
 
@@ -348,7 +952,7 @@ mod test {
         let input = "{ \"a\": \"32\", \"s\": \"well well well\" }";
         let input = Cursor::new(input);
 
-        let mut de = JsonDeserializer { read: input };
+        let mut de = JsonDeserializer::new(input);
 
         let a = A::deserialize(&mut de);
 
@@ -357,4 +961,130 @@ mod test {
         assert_eq!(a.as_ref().unwrap().a, 32);
         assert_eq!(a.as_ref().unwrap().s, "well well well".to_string());
     }
+
+    #[test]
+    fn error_reports_line_and_column() {
+        let input = "[\n\t32,\n\t6x4\n]";
+        let result = Vec::<i32>::from_json(input);
+
+        let err = result.unwrap_err();
+        assert!(matches!(err.kind(), Error::InvalidValue { .. }));
+        assert_eq!(err.line(), Some(3));
+        assert_eq!(err.column(), Some(2));
+        assert_eq!(err.path(), Some("[1]"));
+    }
+
+    #[test]
+    fn error_reports_nested_path() {
+        let input = "{ \"player\": { \"items\": [{ \"slot\": \"1\" }, { \"slot\": \"x\" }] } }";
+        let result =
+            HashMap::<String, HashMap<String, Vec<HashMap<String, u32>>>>::from_json(input);
+
+        let err = result.unwrap_err();
+        assert_eq!(err.path(), Some("player.items[1].slot"));
+        assert_eq!(err.line(), Some(1));
+        assert_eq!(err.column(), Some(52));
+    }
+
+    #[test]
+    fn strings_borrow_from_slice() {
+        let input = "{ \"greeting\": \"hello\", \"farewell\": \"good\\tbye\" }";
+        let map: HashMap<&str, Cow<str>> = FromJson::from_json(input).unwrap();
+
+        assert!(matches!(map["greeting"], Cow::Borrowed("hello")));
+        assert!(matches!(&map["farewell"], Cow::Owned(str) if str == "good\tbye"));
+    }
+
+    #[test]
+    fn borrowed_str_rejects_escaped_and_streamed_strings() {
+        let escaped: Result<Vec<&str>, Error> = FromJson::from_json("[\"a\\\"b\"]");
+        assert!(matches!(
+            escaped.unwrap_err().kind(),
+            Error::InvalidType { .. }
+        ));
+
+        let input = "[\"plain\"]";
+        let mut de = JsonDeserializer::new(Cursor::new(input));
+        let streamed = Vec::<&str>::deserialize(&mut de);
+        assert!(matches!(
+            streamed.unwrap_err().kind(),
+            Error::InvalidType { .. }
+        ));
+    }
+
+    #[test]
+    fn escape_sequences_are_decoded() {
+        let input = r#"["quote \" slash \/ back \\", "\u00e9\ud83d\ude00\n"]"#;
+        let strings = Vec::<String>::from_json(input).unwrap();
+
+        assert_eq!(
+            strings,
+            vec!["quote \" slash / back \\", "\u{e9}\u{1f600}\n"]
+        );
+
+        let err = Vec::<String>::from_json(r#"["\ud83d"]"#).unwrap_err();
+        assert!(matches!(err.kind(), Error::Syntax(_)));
+    }
+
+    #[test]
+    fn escaped_strings_round_trip() {
+        let expected = vec!["line\nbreak".to_string(), "\"quoted\" \\ \u{1}".to_string()];
+        let json = crate::serialize::json::IntoJson::to_json(&expected).unwrap();
+        let result = Vec::<String>::from_json(&json).unwrap();
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn deserialize_at_skips_to_pointer() {
+        let input = "{ \"audio\": { \"volume\": [1, { \"x\": \"]\" }] },\n  \"a/b\": { \"c\": [\"7\", \"8\"] } }";
+        let at = |pointer: &str| {
+            let mut de = JsonDeserializer::new(SliceRead::new(input.as_bytes()));
+            de.deserialize_at::<u32>(pointer)
+        };
+
+        assert_eq!(at("/a~1b/c/1").unwrap(), Some(8));
+        assert_eq!(at("/audio/volume/0").unwrap(), Some(1));
+        assert_eq!(at("/audio/volume/2").unwrap(), None);
+        assert_eq!(at("/audio/missing").unwrap(), None);
+        assert_eq!(at("/audio/volume/x").unwrap(), None);
+
+        let err = at("/audio/volume/1").unwrap_err();
+        assert_eq!(err.path(), Some("audio.volume[1]"));
+
+        let mut de = JsonDeserializer::new(Cursor::new(input));
+        let c: Option<Vec<String>> = de.deserialize_at("/a~1b/c").unwrap();
+        assert_eq!(c, Some(vec!["7".to_string(), "8".to_string()]));
+    }
+
+    /// Hands out at most two bytes per read to exercise buffer boundaries
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> io::Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(2).min(self.0.len());
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn parse_from_reader_test() {
+        let input = "{ \"first\": [\"32\", \"64\"],\n  \"second\": [] }";
+        let map: HashMap<String, Vec<i32>> = from_reader(Trickle(input.as_bytes())).unwrap();
+
+        assert_eq!(map["first"], vec![32, 64]);
+        assert!(map["second"].is_empty());
+    }
+
+    #[test]
+    fn to_writer_and_from_reader_round_trip() {
+        let expected = vec!["A".to_string(), "B".to_string()];
+        let mut buffer = vec![];
+        crate::serialize::json::IntoJson::to_writer(&expected, &mut buffer).unwrap();
+
+        let result: Vec<String> = from_reader(buffer.as_slice()).unwrap();
+        assert_eq!(result, expected);
+    }
 }
@@ -0,0 +1,209 @@
+//! Human readable rendering of located errors
+//!
+//! Meant for people editing configuration files by hand: the offending line of the source is printed
+//! with a caret under the token that failed, followed by what was expected and, for unknown fields,
+//! the closest known field name.
+use std::fmt::Write;
+
+use crate::error::Error;
+
+/// Renders the error together with the part of *source* it points to
+///
+/// *source* must be the input the error was produced from.
+/// Errors without a location are rendered without a snippet.
+///
+/// # Example
+/// ```
+/// use lib_contra::{diagnostic, error::Error};
+///
+/// let source = "{\n\t\"helth\": \"10\"\n}";
+/// let err = Error::unknown_field("helth", &["name", "health"]).at(2, 2, String::new());
+///
+/// let rendered = diagnostic::render(&err, source);
+/// assert!(rendered.contains("did you mean `health`?"));
+/// ```
+pub fn render(err: &Error, source: &str) -> String {
+    let mut out = String::new();
+    let kind = err.kind();
+
+    writeln!(out, "error: {}", headline(kind)).unwrap();
+
+    if let (Some(line), Some(column)) = (err.line(), err.column()) {
+        let path = err.path().unwrap_or_default();
+        match path.is_empty() {
+            true => writeln!(out, " --> line {}, column {}", line, column).unwrap(),
+            false => writeln!(out, " --> line {}, column {} at `{}`", line, column, path).unwrap(),
+        }
+
+        // lines and columns count from 1, anything else has no snippet to show
+        let snippet = match (line.checked_sub(1), column.checked_sub(1)) {
+            (Some(index), Some(offset)) => source.lines().nth(index).map(|text| (text, offset)),
+            _ => None,
+        };
+        if let Some((text, offset)) = snippet {
+            let number = line.to_string();
+            let gutter = " ".repeat(number.len());
+            let text = text.trim_end_matches('\r');
+            let indent: String = text
+                .chars()
+                .take(offset)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let carets = "^".repeat(token_len(text, offset));
+
+            writeln!(out, "{} |", gutter).unwrap();
+            writeln!(out, "{} | {}", number, text).unwrap();
+            writeln!(out, "{} | {}{}", gutter, indent, carets).unwrap();
+        }
+    }
+
+    for note in notes(kind) {
+        writeln!(out, " = {}", note).unwrap();
+    }
+
+    out
+}
+
+fn headline(kind: &Error) -> String {
+    match kind {
+        Error::UnknownField { field, .. } => format!("unknown field `{}`", field),
+        Error::InvalidType { .. } => "invalid type".to_string(),
+        Error::InvalidValue { found, .. } => format!("invalid value {}", found),
+        err => err.to_string(),
+    }
+}
+
+fn notes(kind: &Error) -> Vec<String> {
+    match kind {
+        Error::UnknownField { field, expected } => {
+            let mut notes = vec![];
+            if !expected.is_empty() {
+                notes.push(format!("expected one of `{}`", expected.join("`, `")));
+            }
+            if let Some(suggestion) = suggest(field, expected) {
+                notes.push(format!("help: did you mean `{}`?", suggestion));
+            }
+            notes
+        }
+        Error::InvalidType { found, expected } => {
+            vec![format!("expected {}, found {}", expected, found)]
+        }
+        Error::InvalidValue { expected, .. } => vec![format!("expected {}", expected)],
+        _ => vec![],
+    }
+}
+
+/// Number of characters the token starting after *offset* characters spans
+fn token_len(line: &str, offset: usize) -> usize {
+    let mut chars = line.chars().skip(offset);
+    match chars.next() {
+        Some('"') => match chars.position(|c| c == '"') {
+            Some(end) => end + 2,
+            None => 1,
+        },
+        Some('{') | Some('[') | None => 1,
+        Some(_) => {
+            1 + chars
+                .take_while(|c| !matches!(c, ' ' | '\t' | ',' | ':' | ']' | '}'))
+                .count()
+        }
+    }
+}
+
+/// The candidate closest to *field*, if it is close enough to be a likely typo
+fn suggest<'a>(field: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|candidate| (edit_distance(field, candidate), *candidate))
+        .filter(|(distance, candidate)| {
+            *distance < field.chars().count().max(candidate.len())
+                && *distance <= 2.max(candidate.len() / 3)
+        })
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = match ca == *cb {
+                true => diagonal,
+                false => 1 + diagonal.min(above).min(row[j]),
+            };
+            diagonal = above;
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn edit_distance_works() {
+        assert_eq!(edit_distance("health", "health"), 0);
+        assert_eq!(edit_distance("helth", "health"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn suggests_closest_field() {
+        assert_eq!(suggest("helth", &["name", "health"]), Some("health"));
+        assert_eq!(suggest("slto", &["slot", "stats"]), Some("slot"));
+        assert_eq!(suggest("speed", &["name", "health"]), None);
+    }
+
+    #[test]
+    fn render_points_at_token() {
+        let source = "{\n    \"name\": \"Hero\",\n    \"helth\": \"10\"\n}";
+        let err = Error::unknown_field("helth", &["name", "health"]).at(3, 5, "player".to_string());
+
+        let expected = "\
+error: unknown field `helth`
+ --> line 3, column 5 at `player`
+  |
+3 |     \"helth\": \"10\"
+  |     ^^^^^^^
+ = expected one of `name`, `health`
+ = help: did you mean `health`?
+";
+        assert_eq!(render(&err, source), expected);
+    }
+
+    #[test]
+    fn render_shows_expected_and_found_types() {
+        let source = "{ \"items\": \"sword\" }";
+        let err = Error::invalid_type("str", "vec").at(1, 12, "items".to_string());
+
+        let rendered = render(&err, source);
+        assert!(rendered.contains("1 | { \"items\": \"sword\" }"));
+        assert!(rendered.contains("  |            ^^^^^^^\n"));
+        assert!(rendered.contains(" = expected vec, found str"));
+    }
+
+    #[test]
+    fn render_without_snippet_outside_of_source() {
+        let source = "{ \"items\": \"sword\" }";
+        let err = Error::invalid_type("str", "vec").at(0, 0, String::new());
+
+        let expected = "\
+error: invalid type
+ --> line 0, column 0
+ = expected vec, found str
+";
+        assert_eq!(render(&err, source), expected);
+
+        let err = Error::invalid_type("str", "vec").at(5, 1, String::new());
+        assert!(!render(&err, source).contains('|'));
+    }
+}
@@ -0,0 +1,197 @@
+//! Structural differences between two serializable values
+//!
+//! Both values are captured into [Value]s, so any [Serialize] type can be compared without implementing [PartialEq].
+use std::fmt::{self, Display};
+
+use crate::{
+    error::Error,
+    serialize::Serialize,
+    value::{pointer::Pointer, to_value, Value},
+};
+
+/// A single difference, located by a JSON pointer into the compared values
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// Present in the new value only
+    Added { path: Pointer, value: Value },
+    /// Present in the old value only
+    Removed { path: Pointer, value: Value },
+    /// Present in both but different
+    Modified {
+        path: Pointer,
+        old: Value,
+        new: Value,
+    },
+}
+
+impl Change {
+    pub fn path(&self) -> &Pointer {
+        match self {
+            Change::Added { path, .. }
+            | Change::Removed { path, .. }
+            | Change::Modified { path, .. } => path,
+        }
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added { path, value } => write!(f, "+ {}: {}", path, value),
+            Change::Removed { path, value } => write!(f, "- {}: {}", path, value),
+            Change::Modified { path, old, new } => write!(f, "~ {}: {} -> {}", path, old, new),
+        }
+    }
+}
+
+/// Lists every difference between *old* and *new*
+///
+/// Objects are compared key by key and arrays index by index, anything else is compared as a whole.
+/// The changes are ordered such that applying them one after the other turns *old* into *new*:
+/// surplus array elements are removed starting from the last one.
+///
+/// # Example
+/// ```
+/// use lib_contra::{diff::{diff, Change}, json};
+///
+/// let old = json!({ "name": "Hero", "items": ["sword"] });
+/// let new = json!({ "name": "Hero", "items": ["axe", "shield"] });
+///
+/// let changes = diff(&old, &new).unwrap();
+/// assert_eq!(changes[0].to_string(), "~ /items/0: \"sword\" -> \"axe\"");
+/// assert_eq!(changes[1].to_string(), "+ /items/1: \"shield\"");
+/// ```
+pub fn diff<T: Serialize>(old: &T, new: &T) -> Result<Vec<Change>, Error> {
+    let old = to_value(old)?;
+    let new = to_value(new)?;
+    Ok(diff_values(&old, &new))
+}
+
+/// Like [diff], for values that are already captured
+pub fn diff_values(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = vec![];
+    compare(&Pointer::root(), old, new, &mut changes);
+    changes
+}
+
+fn compare(path: &Pointer, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old) in old.iter() {
+                let path = at(path, key);
+                match new.get(key) {
+                    Some(new) => compare(&path, old, new, changes),
+                    None => changes.push(Change::Removed {
+                        path,
+                        value: old.clone(),
+                    }),
+                }
+            }
+            for (key, new) in new.iter().filter(|(key, _)| !old.contains_key(*key)) {
+                changes.push(Change::Added {
+                    path: at(path, key),
+                    value: new.clone(),
+                });
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for (i, (old, new)) in old.iter().zip(new.iter()).enumerate() {
+                compare(&at(path, &i.to_string()), old, new, changes);
+            }
+            for (i, old) in old.iter().enumerate().skip(new.len()).rev() {
+                changes.push(Change::Removed {
+                    path: at(path, &i.to_string()),
+                    value: old.clone(),
+                });
+            }
+            for (i, new) in new.iter().enumerate().skip(old.len()) {
+                changes.push(Change::Added {
+                    path: at(path, &i.to_string()),
+                    value: new.clone(),
+                });
+            }
+        }
+        (old, new) if old != new => changes.push(Change::Modified {
+            path: path.clone(),
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+fn at(path: &Pointer, token: &str) -> Pointer {
+    let mut path = path.clone();
+    path.push(token);
+    path
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{json, position::Position, serialize::Serializer};
+
+    #[test]
+    fn equal_values_have_no_changes() {
+        let value = json!({ "a": [1, { "b": null }], "c": "d" });
+        assert!(diff_values(&value, &value.clone()).is_empty());
+    }
+
+    #[test]
+    fn lists_added_removed_and_modified() {
+        let old = json!({ "keep": 1, "gone": true, "list": [1, 2, 3], "kind": [1] });
+        let new = json!({ "keep": 1, "new": "x", "list": [1, 5], "kind": { "a": 1 } });
+
+        let changes = diff_values(&old, &new);
+        let rendered: Vec<String> = changes.iter().map(Change::to_string).collect();
+        assert_eq!(
+            rendered,
+            vec![
+                "- /gone: true",
+                "~ /kind: [1] -> {\"a\":1}",
+                "~ /list/1: 2 -> 5",
+                "- /list/2: 3",
+                "+ /new: \"x\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn works_without_partial_eq() {
+        struct Level {
+            name: String,
+            spawns: Vec<u32>,
+        }
+
+        impl Serialize for Level {
+            fn serialize<S: Serializer>(
+                &self,
+                ser: &mut S,
+                _pos: &Position,
+            ) -> crate::error::SuccessResult {
+                ser.begin_struct("Level", 2)?;
+                ser.serialize_field("name", &self.name, &Position::Trailing)?;
+                ser.serialize_field("spawns", &self.spawns, &Position::Closing)?;
+                ser.end_struct("Level")
+            }
+        }
+
+        let old = Level {
+            name: "Cave".to_string(),
+            spawns: vec![1, 2],
+        };
+        let new = Level {
+            name: "Cave".to_string(),
+            spawns: vec![1, 2, 3],
+        };
+
+        let changes = diff(&old, &new).unwrap();
+        assert_eq!(
+            changes,
+            vec![Change::Added {
+                path: Pointer::parse("/spawns/2").unwrap(),
+                value: json!(3u32),
+            }]
+        );
+    }
+}
@@ -1,8 +1,291 @@
+//! Error type shared by all serializers and deserializers
 use std::{
-    error::Error,
-    io::{self},
+    fmt::{self, Display},
+    io,
+    path::PathBuf,
+    str::Utf8Error,
 };
 
-pub type AnyError = Box<dyn Error>;
-pub type SuccessResult = Result<(), AnyError>;
+/// Any error that can occur while serializing or deserializing
+///
+/// The variants allow callers to tell different kinds of failures apart,
+/// e.g. a missing field from a failed file read.
+/// [Visitor](crate::deserialize::Visitor) implementations should build their errors through the
+/// provided constructors such as [Error::custom] or [Error::invalid_type].
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing the underlying source failed
+    Io(io::Error),
+    /// The input is not well formed
+    Syntax(String),
+    /// A required field was not present
+    MissingField(&'static str),
+    /// A field was present that the target type does not know about
+    UnknownField {
+        field: String,
+        expected: &'static [&'static str],
+    },
+    /// A field was present more than once
+    DuplicateField(&'static str),
+    /// A value of the wrong type was found
+    InvalidType { found: String, expected: String },
+    /// A value of the right type but with an unacceptable content was found
+    InvalidValue { found: String, expected: String },
+    /// A sequence had an unexpected number of elements
+    InvalidLength { len: usize, expected: String },
+    /// No format is known for the extension of the path
+    UnknownFormat(PathBuf),
+    /// A field violated a constraint checked by [Validate](crate::validate::Validate)
+    Validation { path: String, reason: String },
+    /// Help was requested on the command line, carries the generated help text
+    Help(String),
+    /// Any other error
+    Custom(String),
+    /// Another error annotated with where in the input it occurred
+    Located {
+        source: Box<Error>,
+        line: usize,
+        column: usize,
+        path: String,
+    },
+}
+
+impl Error {
+    pub fn custom<T: Display>(msg: T) -> Self {
+        Error::Custom(msg.to_string())
+    }
+
+    pub fn syntax<T: Display>(msg: T) -> Self {
+        Error::Syntax(msg.to_string())
+    }
+
+    pub fn missing_field(field: &'static str) -> Self {
+        Error::MissingField(field)
+    }
+
+    pub fn unknown_field(field: &str, expected: &'static [&'static str]) -> Self {
+        Error::UnknownField {
+            field: field.to_string(),
+            expected,
+        }
+    }
+
+    pub fn duplicate_field(field: &'static str) -> Self {
+        Error::DuplicateField(field)
+    }
+
+    pub fn invalid_type(found: &str, expected: &str) -> Self {
+        Error::InvalidType {
+            found: found.to_string(),
+            expected: expected.to_string(),
+        }
+    }
+
+    pub fn invalid_value<T: Display>(found: T, expected: &str) -> Self {
+        Error::InvalidValue {
+            found: found.to_string(),
+            expected: expected.to_string(),
+        }
+    }
+
+    pub fn invalid_length(len: usize, expected: &str) -> Self {
+        Error::InvalidLength {
+            len,
+            expected: expected.to_string(),
+        }
+    }
+
+    pub fn validation<T: Display>(path: &str, reason: T) -> Self {
+        Error::Validation {
+            path: path.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Annotates the error with a location unless it already carries one
+    pub fn at(self, line: usize, column: usize, path: String) -> Self {
+        match self {
+            Error::Located { .. } => self,
+            err => Error::Located {
+                source: Box::new(err),
+                line,
+                column,
+                path,
+            },
+        }
+    }
+
+    /// The error without any location annotation
+    pub fn kind(&self) -> &Error {
+        match self {
+            Error::Located { source, .. } => source.kind(),
+            err => err,
+        }
+    }
+
+    /// 1-based line in the input at which the error occurred
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Error::Located { line, .. } => Some(*line),
+            _ => None,
+        }
+    }
+
+    /// 1-based column in the input at which the error occurred
+    pub fn column(&self) -> Option<usize> {
+        match self {
+            Error::Located { column, .. } => Some(*column),
+            _ => None,
+        }
+    }
+
+    /// Logical path of the value that failed, e.g. `player.items[3].slot`
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Error::Located { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "io error: {}", err),
+            Error::Syntax(msg) => write!(f, "syntax error: {}", msg),
+            Error::MissingField(field) => write!(f, "missing field `{}`", field),
+            Error::UnknownField { field, expected } => {
+                write!(f, "unknown field `{}`", field)?;
+                match expected.len() {
+                    0 => write!(f, ", there are no fields"),
+                    _ => write!(f, ", expected one of `{}`", expected.join("`, `")),
+                }
+            }
+            Error::DuplicateField(field) => write!(f, "duplicate field `{}`", field),
+            Error::InvalidType { found, expected } => {
+                write!(f, "invalid type: found {}, expected {}", found, expected)
+            }
+            Error::InvalidValue { found, expected } => {
+                write!(f, "invalid value: found {}, expected {}", found, expected)
+            }
+            Error::InvalidLength { len, expected } => {
+                write!(f, "invalid length {}, expected {}", len, expected)
+            }
+            Error::UnknownFormat(path) => write!(
+                f,
+                "unknown format of `{}`, no registered format fits its extension or content",
+                path.display()
+            ),
+            Error::Validation { path, reason } => write!(f, "invalid `{}`: {}", path, reason),
+            Error::Help(help) => write!(f, "{}", help),
+            Error::Custom(msg) => write!(f, "{}", msg),
+            Error::Located {
+                source,
+                line,
+                column,
+                path,
+            } => {
+                write!(f, "{} at line {} column {}", source, line, column)?;
+                match path.is_empty() {
+                    true => Ok(()),
+                    false => write!(f, " (`{}`)", path),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Located { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+/// IO errors are cloned by their kind and message, the underlying OS error is not kept
+impl Clone for Error {
+    fn clone(&self) -> Self {
+        match self {
+            Error::Io(err) => Error::Io(io::Error::new(err.kind(), err.to_string())),
+            Error::Syntax(msg) => Error::Syntax(msg.clone()),
+            Error::MissingField(field) => Error::MissingField(field),
+            Error::UnknownField { field, expected } => Error::UnknownField {
+                field: field.clone(),
+                expected,
+            },
+            Error::DuplicateField(field) => Error::DuplicateField(field),
+            Error::InvalidType { found, expected } => Error::InvalidType {
+                found: found.clone(),
+                expected: expected.clone(),
+            },
+            Error::InvalidValue { found, expected } => Error::InvalidValue {
+                found: found.clone(),
+                expected: expected.clone(),
+            },
+            Error::InvalidLength { len, expected } => Error::InvalidLength {
+                len: *len,
+                expected: expected.clone(),
+            },
+            Error::UnknownFormat(path) => Error::UnknownFormat(path.clone()),
+            Error::Validation { path, reason } => Error::Validation {
+                path: path.clone(),
+                reason: reason.clone(),
+            },
+            Error::Help(help) => Error::Help(help.clone()),
+            Error::Custom(msg) => Error::Custom(msg.clone()),
+            Error::Located {
+                source,
+                line,
+                column,
+                path,
+            } => Error::Located {
+                source: source.clone(),
+                line: *line,
+                column: *column,
+                path: path.clone(),
+            },
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<Utf8Error> for Error {
+    fn from(err: Utf8Error) -> Self {
+        Error::Syntax(err.to_string())
+    }
+}
+
+/// Former name of [Error], kept so existing signatures keep compiling
+pub type AnyError = Error;
+pub type SuccessResult = Result<(), Error>;
 pub type IoResult = Result<(), io::Error>;
+
+#[cfg(test)]
+mod test {
+    use super::Error;
+
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+
+    #[test]
+    fn error_is_send_and_sync() {
+        assert_send_sync::<Error>();
+    }
+
+    #[test]
+    fn error_kinds_can_be_matched() {
+        let err: Error = std::io::Error::from(std::io::ErrorKind::NotFound).into();
+        assert!(matches!(err, Error::Io(_)));
+
+        let err = Error::missing_field("health");
+        assert!(matches!(err, Error::MissingField("health")));
+        assert_eq!(err.to_string(), "missing field `health`");
+    }
+}
@@ -1,7 +1,7 @@
 use std::io;
 
 use crate::{
-    error::{AnyError, IoResult, SuccessResult},
+    error::{Error, IoResult, SuccessResult},
     position::Position,
 };
 
@@ -12,11 +12,15 @@ macro_rules! decl_write_primitive {
 }
 
 pub trait WriteFormatter<W: io::Write> {
+    /// Starts at the current position, which the enclosing field or item has already placed
     fn write_struct_begin(&mut self, write: &mut W, name: &str, fields: usize) -> IoResult;
     fn write_struct_end(&mut self, write: &mut W, name: &str) -> IoResult;
 
     fn write_collection_begin(&mut self, write: &mut W, name: &str, size: usize) -> IoResult;
     fn write_collection_end(&mut self, write: &mut W, name: &str) -> IoResult;
+    /// Surround every element of a collection, e.g. to indent it and separate it from the next one
+    fn write_item_begin(&mut self, write: &mut W) -> IoResult;
+    fn write_item_end(&mut self, write: &mut W, pos: &Position) -> IoResult;
 
     fn write_field_assignnment_begin(&mut self, write: &mut W) -> IoResult;
     fn write_field_key(&mut self, write: &mut W, name: &str) -> IoResult;
@@ -38,11 +42,13 @@ pub trait WriteFormatter<W: io::Write> {
     decl_write_primitive!(usize, write_usize);
     decl_write_primitive!(isize, write_isize);
     decl_write_primitive!(str, write_str);
+    decl_write_primitive!(bool, write_bool);
+    fn write_null(&mut self, write: &mut W) -> IoResult;
 }
 
 macro_rules! decl_read_primitive {
     ($ttype: ident, $des_func: ident) => {
-        fn $des_func(&mut self, read: &mut R) -> Result<$ttype, AnyError>;
+        fn $des_func(&mut self, read: &mut R) -> Result<$ttype, Error>;
     };
 }
 
@@ -0,0 +1,516 @@
+//! Configuration assembled from several layers, e.g. defaults, a user file and environment overrides
+//!
+//! Every layer is captured into a [Value], later layers are deep merged over earlier ones
+//! and the result is deserialized through the regular [Deserialize](crate::deserialize::Deserialize) implementation.
+//! Strings of [textual](Source::is_textual) layers are parsed by the type they are deserialized into.
+use std::{
+    collections::{btree_map, BTreeMap},
+    io, iter,
+    path::PathBuf,
+    vec,
+};
+
+use crate::{
+    deserialize::{
+        env::EnvDeserializer, Deserialize, DeserializeOwned, Deserializer, MapAccess, SeqAccess,
+        Visitor,
+    },
+    error::Error,
+    persistent::{deserializer_factory, read_bytes_file},
+    value::{pointer::Pointer, Value},
+};
+
+/// A single layer of a [Layered] configuration
+pub trait Source {
+    /// Name of the layer as reported by [Origins]
+    fn name(&self) -> String;
+
+    /// The content of the layer, *None* if it is absent and may be skipped
+    fn load(&self) -> Result<Option<Value>, Error>;
+
+    /// Whether the values of the layer are strings for the target type to parse, like environment variables
+    ///
+    /// Such a string is read as a number or bool where one is expected
+    /// and as a comma separated list where a sequence is.
+    fn is_textual(&self) -> bool {
+        false
+    }
+}
+
+/// A file whose format is selected by its extension, like [Persistent](crate::persistent::Persistent)
+pub struct File {
+    path: PathBuf,
+    required: bool,
+}
+
+impl File {
+    /// A file that must exist
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            required: true,
+        }
+    }
+
+    /// A file that is skipped if it does not exist
+    pub fn optional(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            required: false,
+        }
+    }
+}
+
+impl Source for File {
+    fn name(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn load(&self) -> Result<Option<Value>, Error> {
+        match read_bytes_file(&self.path) {
+            Ok(content) => deserializer_factory(&content, &self.path).map(Some),
+            Err(err) if err.kind() == io::ErrorKind::NotFound && !self.required => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// A layer given in memory, e.g. built with [json](crate::json)
+impl Source for (&str, Value) {
+    fn name(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn load(&self) -> Result<Option<Value>, Error> {
+        Ok(Some(self.1.clone()))
+    }
+}
+
+/// How arrays present in two layers are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArrayStrategy {
+    /// The later array replaces the earlier one
+    #[default]
+    Replace,
+    /// The elements of the later array are appended to the earlier one
+    Append,
+    /// Elements are deep merged index by index, surplus elements of the later array are appended
+    Merge,
+}
+
+/// Builds a configuration from several [Source]s, later sources override earlier ones
+///
+/// Objects are merged key by key, arrays according to the [ArrayStrategy] and anything else,
+/// including `null`, replaces the earlier value.
+///
+/// # Example
+/// ```
+/// use lib_contra::{json, layered::Layered};
+///
+/// let defaults = json!({ "graphics": { "width": 1280, "height": 720 }, "volume": 5 });
+/// let user = json!({ "graphics": { "width": 1920 } });
+///
+/// let (merged, origins) = Layered::new()
+///     .source(("defaults", defaults))
+///     .source(("user", user))
+///     .merge()
+///     .unwrap();
+///
+/// assert_eq!(merged, json!({ "graphics": { "width": 1920, "height": 720 }, "volume": 5 }));
+/// assert_eq!(origins.get("/graphics/width"), Some("user"));
+/// assert_eq!(origins.get("/graphics/height"), Some("defaults"));
+/// ```
+#[derive(Default)]
+pub struct Layered {
+    sources: Vec<Box<dyn Source>>,
+    arrays: ArrayStrategy,
+}
+
+impl Layered {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a layer on top of the previous ones
+    pub fn source(mut self, source: impl Source + 'static) -> Self {
+        self.sources.push(Box::new(source));
+        self
+    }
+
+    /// Adds a file that must exist
+    pub fn file(self, path: impl Into<PathBuf>) -> Self {
+        self.source(File::new(path))
+    }
+
+    /// Adds a file that is skipped if it does not exist
+    pub fn optional_file(self, path: impl Into<PathBuf>) -> Self {
+        self.source(File::optional(path))
+    }
+
+    /// Adds the environment variables starting with *prefix*, see [EnvDeserializer]
+    pub fn env(self, prefix: &str) -> Self {
+        self.source(EnvDeserializer::new(prefix))
+    }
+
+    pub fn arrays(mut self, strategy: ArrayStrategy) -> Self {
+        self.arrays = strategy;
+        self
+    }
+
+    /// Loads and merges all layers
+    pub fn merge(&self) -> Result<(Value, Origins), Error> {
+        let mut merged = Value::Object(BTreeMap::new());
+        let mut origins = Origins::default();
+        for source in self.sources.iter() {
+            if let Some(layer) = source.load()? {
+                let mut merge = Merge {
+                    layer: source.name(),
+                    arrays: self.arrays,
+                    origins: &mut origins,
+                };
+                merge.merge(&mut merged, layer, &mut Pointer::root());
+            }
+        }
+        Ok((merged, origins))
+    }
+
+    /// Loads, merges and deserializes all layers
+    pub fn load<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        self.load_with_origins().map(|(value, _)| value)
+    }
+
+    /// Like [load](Layered::load), additionally reporting which layer each value came from
+    pub fn load_with_origins<T: DeserializeOwned>(&self) -> Result<(T, Origins), Error> {
+        let (merged, origins) = self.merge()?;
+        let textual: Vec<String> = self
+            .sources
+            .iter()
+            .filter(|source| source.is_textual())
+            .map(|source| source.name())
+            .collect();
+        let value = T::deserialize(MergedDeserializer {
+            value: merged,
+            path: Pointer::root(),
+            origins: &origins,
+            textual: &textual,
+        })?;
+        Ok((value, origins))
+    }
+}
+
+/// The layer each value of a merged configuration came from
+///
+/// Values are addressed by JSON pointers, only values that are not objects or arrays are tracked,
+/// except for empty ones.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Origins {
+    layers: BTreeMap<String, String>,
+}
+
+impl Origins {
+    /// Name of the layer the value at *pointer* came from
+    pub fn get(&self, pointer: &str) -> Option<&str> {
+        self.layers.get(pointer).map(String::as_str)
+    }
+
+    /// Every tracked pointer with the name of its layer, ordered by pointer
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.layers
+            .iter()
+            .map(|(pointer, layer)| (pointer.as_str(), layer.as_str()))
+    }
+
+    /// Forgets the origins of the value at *pointer* and everything inside of it
+    fn clear(&mut self, pointer: &str) {
+        let nested = format!("{}/", pointer);
+        self.layers
+            .retain(|tracked, _| tracked != pointer && !tracked.starts_with(&nested));
+    }
+}
+
+/// Merges a single layer, recording the origin of every value it sets
+struct Merge<'o> {
+    layer: String,
+    arrays: ArrayStrategy,
+    origins: &'o mut Origins,
+}
+
+impl Merge<'_> {
+    fn merge(&mut self, target: &mut Value, layer: Value, path: &mut Pointer) {
+        match (target, layer) {
+            (Value::Object(target), Value::Object(layer)) => {
+                for (key, value) in layer.into_iter() {
+                    path.push(key.as_str());
+                    match target.get_mut(&key) {
+                        Some(target) => self.merge(target, value, path),
+                        None => {
+                            self.track(&value, path);
+                            target.insert(key, value);
+                        }
+                    }
+                    path.pop();
+                }
+            }
+            (Value::Array(target), Value::Array(layer))
+                if self.arrays != ArrayStrategy::Replace =>
+            {
+                for (i, value) in layer.into_iter().enumerate() {
+                    let index = match self.arrays {
+                        ArrayStrategy::Append => target.len(),
+                        _ => i,
+                    };
+                    path.push(index.to_string());
+                    match target.get_mut(index) {
+                        Some(target) => self.merge(target, value, path),
+                        None => {
+                            self.track(&value, path);
+                            target.push(value);
+                        }
+                    }
+                    path.pop();
+                }
+            }
+            (target, layer) => {
+                self.origins.clear(&path.to_string());
+                self.track(&layer, path);
+                *target = layer;
+            }
+        }
+    }
+
+    /// Records the layer as origin of every value within *value*
+    fn track(&mut self, value: &Value, path: &mut Pointer) {
+        let children: Vec<(String, &Value)> = match value {
+            Value::Object(object) => object.iter().map(|(k, v)| (k.clone(), v)).collect(),
+            Value::Array(array) => array
+                .iter()
+                .enumerate()
+                .map(|(i, v)| (i.to_string(), v))
+                .collect(),
+            _ => vec![],
+        };
+        if children.is_empty() {
+            self.origins
+                .layers
+                .insert(path.to_string(), self.layer.clone());
+        }
+        for (token, child) in children {
+            path.push(token);
+            self.track(child, path);
+            path.pop();
+        }
+    }
+}
+
+/// Deserializes a merged configuration, leaving the strings of textual layers to the target type
+struct MergedDeserializer<'a> {
+    value: Value,
+    path: Pointer,
+    origins: &'a Origins,
+    /// Names of the [textual](Source::is_textual) layers
+    textual: &'a [String],
+}
+
+impl MergedDeserializer<'_> {
+    fn child(&self, token: String, value: Value) -> Self {
+        let mut path = self.path.clone();
+        path.push(token);
+        Self {
+            value,
+            path,
+            origins: self.origins,
+            textual: self.textual,
+        }
+    }
+
+    /// The string of a textual layer, parsed like a variable
+    fn text(&self) -> Option<EnvDeserializer> {
+        let path = self.path.to_string();
+        match (&self.value, self.origins.get(&path)) {
+            (Value::String(text), Some(layer)) if self.textual.iter().any(|t| t == layer) => {
+                Some(EnvDeserializer::text(&path, text.clone()))
+            }
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_deserialize_text {
+    ($deserialize_fn: ident) => {
+        fn $deserialize_fn<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+            match self.text() {
+                Some(text) => text.$deserialize_fn(v),
+                None => self.value.$deserialize_fn(v),
+            }
+        }
+    };
+}
+
+impl<'de> Deserializer<'de> for MergedDeserializer<'_> {
+    fn deserialize_map<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        match self.value {
+            Value::Object(object) => v.visit_map(MergedMap {
+                parent: MergedDeserializer {
+                    value: Value::Null,
+                    ..self
+                },
+                iter: object.into_iter(),
+                value: None,
+            }),
+            other => other.deserialize_map(v),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        if let Some(text) = self.text() {
+            return text.deserialize_seq(v);
+        }
+        match self.value {
+            Value::Array(array) => v.visit_seq(MergedSeq {
+                parent: MergedDeserializer {
+                    value: Value::Null,
+                    ..self
+                },
+                iter: array.into_iter().enumerate(),
+            }),
+            other => other.deserialize_seq(v),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        self.deserialize_map(v)
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        match self.value {
+            Value::Object(_) => self.deserialize_map(v),
+            Value::Array(_) => self.deserialize_seq(v),
+            other => other.deserialize_any(v),
+        }
+    }
+
+    impl_deserialize_text!(deserialize_str);
+    impl_deserialize_text!(deserialize_bool);
+    impl_deserialize_text!(deserialize_i8);
+    impl_deserialize_text!(deserialize_i16);
+    impl_deserialize_text!(deserialize_i32);
+    impl_deserialize_text!(deserialize_i64);
+    impl_deserialize_text!(deserialize_i128);
+    impl_deserialize_text!(deserialize_isize);
+    impl_deserialize_text!(deserialize_u8);
+    impl_deserialize_text!(deserialize_u16);
+    impl_deserialize_text!(deserialize_u32);
+    impl_deserialize_text!(deserialize_u64);
+    impl_deserialize_text!(deserialize_u128);
+    impl_deserialize_text!(deserialize_usize);
+    impl_deserialize_text!(deserialize_f32);
+    impl_deserialize_text!(deserialize_f64);
+}
+
+struct MergedMap<'a> {
+    /// Locates the values of the map
+    parent: MergedDeserializer<'a>,
+    iter: btree_map::IntoIter<String, Value>,
+    value: Option<MergedDeserializer<'a>>,
+}
+
+impl<'de> MapAccess<'de> for MergedMap<'_> {
+    fn next_value<V: Deserialize<'de>>(&mut self) -> Result<V, Error> {
+        match self.value.take() {
+            Some(value) => V::deserialize(value),
+            None => Err(Error::custom("requested a map value before its key")),
+        }
+    }
+
+    fn next_key<K: Deserialize<'de>>(&mut self) -> Result<Option<K>, Error> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(self.parent.child(key.clone(), value));
+                K::deserialize(Value::String(key)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+struct MergedSeq<'a> {
+    /// Locates the items of the sequence
+    parent: MergedDeserializer<'a>,
+    iter: iter::Enumerate<vec::IntoIter<Value>>,
+}
+
+impl<'de> SeqAccess<'de> for MergedSeq<'_> {
+    fn next_value<V: Deserialize<'de>>(&mut self) -> Result<Option<V>, Error> {
+        self.iter
+            .next()
+            .map(|(i, value)| V::deserialize(self.parent.child(i.to_string(), value)))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::json;
+
+    fn layered(arrays: ArrayStrategy) -> Layered {
+        Layered::new()
+            .source((
+                "defaults",
+                json!({ "keys": ["w", "a"], "audio": { "volume": 5, "muted": false } }),
+            ))
+            .source(("user", json!({ "keys": ["up"], "audio": { "volume": 7 } })))
+            .arrays(arrays)
+    }
+
+    #[test]
+    fn nested_objects_are_merged_key_by_key() {
+        let (merged, origins) = layered(ArrayStrategy::Replace).merge().unwrap();
+        assert_eq!(
+            merged,
+            json!({ "keys": ["up"], "audio": { "volume": 7, "muted": false } })
+        );
+        assert_eq!(
+            origins.iter().collect::<Vec<_>>(),
+            vec![
+                ("/audio/muted", "defaults"),
+                ("/audio/volume", "user"),
+                ("/keys/0", "user"),
+            ]
+        );
+    }
+
+    #[test]
+    fn array_strategies() {
+        let (merged, origins) = layered(ArrayStrategy::Append).merge().unwrap();
+        assert_eq!(merged["keys"], json!(["w", "a", "up"]));
+        assert_eq!(origins.get("/keys/0"), Some("defaults"));
+        assert_eq!(origins.get("/keys/2"), Some("user"));
+
+        let (merged, _) = layered(ArrayStrategy::Merge).merge().unwrap();
+        assert_eq!(merged["keys"], json!(["up", "a"]));
+    }
+
+    #[test]
+    fn replaced_values_forget_nested_origins() {
+        let (merged, origins) = Layered::new()
+            .source(("defaults", json!({ "audio": { "volume": 5 } })))
+            .source(("user", json!({ "audio": "off" })))
+            .merge()
+            .unwrap();
+        assert_eq!(merged, json!({ "audio": "off" }));
+        assert_eq!(origins.iter().collect::<Vec<_>>(), vec![("/audio", "user")]);
+    }
+
+    #[test]
+    fn missing_files_are_only_skipped_if_optional() {
+        let layered = Layered::new()
+            .source(("defaults", json!({ "volume": 5 })))
+            .optional_file("does/not/exist.json");
+        let volume: std::collections::HashMap<String, u32> = layered.load().unwrap();
+        assert_eq!(volume["volume"], 5);
+
+        let err = layered.file("does/not/exist.json").merge().unwrap_err();
+        assert!(matches!(err, Error::Io(_)));
+    }
+}
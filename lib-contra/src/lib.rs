@@ -3,17 +3,26 @@
 //! Provides the function and traits needed for the serialization and deserialization of any arbitrary object.
 
 pub mod deserialize;
+pub mod diagnostic;
+pub mod diff;
 pub mod error;
 pub mod formatter;
+pub mod layered;
+pub mod migrate;
+pub mod patch;
 pub mod persistent;
 pub mod position;
+pub mod schema;
 pub mod serialize;
+pub mod validate;
+pub mod value;
+pub mod watch;
 
 #[cfg(test)]
 mod test {
     use crate::{
         deserialize::{json::FromJson, Deserialize, Visitor},
-        error::SuccessResult,
+        error::{Error, SuccessResult},
         position::Position,
         serialize::{json::IntoJson, Serialize, Serializer},
     };
@@ -85,10 +94,12 @@ mod test {
         }
     }
 
-    impl Deserialize for PrimitiveDataTypesStruct {
-        fn deserialize<D: crate::deserialize::Deserializer>(
-            de: D,
-        ) -> Result<Self, crate::error::AnyError> {
+    impl<'de> Deserialize<'de> for PrimitiveDataTypesStruct {
+        fn deserialize<D: crate::deserialize::Deserializer<'de>>(de: D) -> Result<Self, Error> {
+            const FIELDS: &[&str] = &[
+                "i8", "i16", "i32", "i64", "i128", "u8", "u16", "u32", "u64", "u128", "f32", "f64",
+                "usize", "isize", "string",
+            ];
             enum Field {
                 I8,
                 I16,
@@ -106,19 +117,19 @@ mod test {
                 Isize,
                 String,
             }
-            impl Deserialize for Field {
-                fn deserialize<D: crate::deserialize::Deserializer>(
+            impl<'de> Deserialize<'de> for Field {
+                fn deserialize<D: crate::deserialize::Deserializer<'de>>(
                     de: D,
-                ) -> Result<Self, crate::error::AnyError> {
+                ) -> Result<Self, Error> {
                     struct FieldVisitor {}
-                    impl Visitor for FieldVisitor {
+                    impl<'de> Visitor<'de> for FieldVisitor {
                         type Value = Field;
 
                         fn expected_a(self) -> String {
                             "PrimitiveDataTypesStruct field".to_string()
                         }
 
-                        fn visit_str(self, v: &str) -> Result<Self::Value, crate::error::AnyError> {
+                        fn visit_str(self, v: &str) -> Result<Self::Value, Error> {
                             match v {
                                 "i8" => Ok(Field::I8),
                                 "i16" => Ok(Field::I16),
@@ -135,7 +146,7 @@ mod test {
                                 "usize" => Ok(Field::Usize),
                                 "isize" => Ok(Field::Isize),
                                 "string" => Ok(Field::String),
-                                val => Err(format!("unknown field {}", val).into()),
+                                val => Err(Error::unknown_field(val, FIELDS)),
                             }
                         }
                     }
@@ -144,17 +155,17 @@ mod test {
             }
 
             struct PrimitiveDataTypesStructVisitor {}
-            impl Visitor for PrimitiveDataTypesStructVisitor {
+            impl<'de> Visitor<'de> for PrimitiveDataTypesStructVisitor {
                 type Value = PrimitiveDataTypesStruct;
 
                 fn expected_a(self) -> String {
                     "PrimitiveDataTypesStruct".to_string()
                 }
 
-                fn visit_map<M: crate::deserialize::MapAccess>(
+                fn visit_map<M: crate::deserialize::MapAccess<'de>>(
                     self,
                     mut map: M,
-                ) -> Result<Self::Value, crate::error::AnyError> {
+                ) -> Result<Self::Value, Error> {
                     let mut i8 = None;
                     let mut i16 = None;
                     let mut i32 = None;
@@ -175,129 +186,129 @@ mod test {
                         match key {
                             Field::I8 => {
                                 if i8.is_some() {
-                                    return Err("duplicate field i8".into());
+                                    return Err(Error::duplicate_field("i8"));
                                 }
                                 i8 = Some(map.next_value()?)
                             }
                             Field::I16 => {
                                 if i16.is_some() {
-                                    return Err("duplicate field i16".into());
+                                    return Err(Error::duplicate_field("i16"));
                                 }
                                 i16 = Some(map.next_value()?)
                             }
                             Field::I32 => {
                                 if i32.is_some() {
-                                    return Err("duplicate field i32".into());
+                                    return Err(Error::duplicate_field("i32"));
                                 }
                                 i32 = Some(map.next_value()?)
                             }
                             Field::I64 => {
                                 if i64.is_some() {
-                                    return Err("duplicate field i64".into());
+                                    return Err(Error::duplicate_field("i64"));
                                 }
                                 i64 = Some(map.next_value()?)
                             }
                             Field::I128 => {
                                 if i128.is_some() {
-                                    return Err("duplicate field i128".into());
+                                    return Err(Error::duplicate_field("i128"));
                                 }
                                 i128 = Some(map.next_value()?)
                             }
                             Field::U8 => {
                                 if u8.is_some() {
-                                    return Err("duplicate field u8".into());
+                                    return Err(Error::duplicate_field("u8"));
                                 }
                                 u8 = Some(map.next_value()?)
                             }
                             Field::U16 => {
                                 if u16.is_some() {
-                                    return Err("duplicate field u16".into());
+                                    return Err(Error::duplicate_field("u16"));
                                 }
                                 u16 = Some(map.next_value()?)
                             }
                             Field::U32 => {
                                 if u32.is_some() {
-                                    return Err("duplicate field u32".into());
+                                    return Err(Error::duplicate_field("u32"));
                                 }
                                 u32 = Some(map.next_value()?)
                             }
                             Field::U64 => {
                                 if u64.is_some() {
-                                    return Err("duplicate field u64".into());
+                                    return Err(Error::duplicate_field("u64"));
                                 }
                                 u64 = Some(map.next_value()?)
                             }
                             Field::U128 => {
                                 if u128.is_some() {
-                                    return Err("duplicate field u128".into());
+                                    return Err(Error::duplicate_field("u128"));
                                 }
                                 u128 = Some(map.next_value()?)
                             }
                             Field::F32 => {
                                 if f32.is_some() {
-                                    return Err("duplicate field f32".into());
+                                    return Err(Error::duplicate_field("f32"));
                                 }
                                 f32 = Some(map.next_value()?)
                             }
                             Field::F64 => {
                                 if f64.is_some() {
-                                    return Err("duplicate field f64".into());
+                                    return Err(Error::duplicate_field("f64"));
                                 }
                                 f64 = Some(map.next_value()?)
                             }
                             Field::Usize => {
                                 if usize.is_some() {
-                                    return Err("duplicate field usize".into());
+                                    return Err(Error::duplicate_field("usize"));
                                 }
                                 usize = Some(map.next_value()?)
                             }
                             Field::Isize => {
                                 if isize.is_some() {
-                                    return Err("duplicate field isize".into());
+                                    return Err(Error::duplicate_field("isize"));
                                 }
                                 isize = Some(map.next_value()?)
                             }
                             Field::String => {
                                 if string.is_some() {
-                                    return Err("duplicate field string".into());
+                                    return Err(Error::duplicate_field("string"));
                                 }
                                 string = Some(map.next_value()?)
                             }
                         }
                     }
 
-                    let i8 = i8.ok_or_else(|| "missing field i8")?;
-                    let i16 = i16.ok_or_else(|| "missing field i16")?;
-                    let i32 = i32.ok_or_else(|| "missing field i32")?;
-                    let i64 = i64.ok_or_else(|| "missing field i64")?;
-                    let i128 = i128.ok_or_else(|| "missing field i128")?;
-                    let u8 = u8.ok_or_else(|| "missing field u8")?;
-                    let u16 = u16.ok_or_else(|| "missing field u16")?;
-                    let u32 = u32.ok_or_else(|| "missing field u32")?;
-                    let u64 = u64.ok_or_else(|| "missing field u64")?;
-                    let u128 = u128.ok_or_else(|| "missing field u128")?;
-                    let f32 = f32.ok_or_else(|| "missing field f32")?;
-                    let f64 = f64.ok_or_else(|| "missing field f64")?;
-                    let usize = usize.ok_or_else(|| "missing field usize")?;
-                    let isize = isize.ok_or_else(|| "missing field isize")?;
-                    let string = string.ok_or_else(|| "missing field string")?;
+                    let i8 = i8.ok_or_else(|| Error::missing_field("i8"))?;
+                    let i16 = i16.ok_or_else(|| Error::missing_field("i16"))?;
+                    let i32 = i32.ok_or_else(|| Error::missing_field("i32"))?;
+                    let i64 = i64.ok_or_else(|| Error::missing_field("i64"))?;
+                    let i128 = i128.ok_or_else(|| Error::missing_field("i128"))?;
+                    let u8 = u8.ok_or_else(|| Error::missing_field("u8"))?;
+                    let u16 = u16.ok_or_else(|| Error::missing_field("u16"))?;
+                    let u32 = u32.ok_or_else(|| Error::missing_field("u32"))?;
+                    let u64 = u64.ok_or_else(|| Error::missing_field("u64"))?;
+                    let u128 = u128.ok_or_else(|| Error::missing_field("u128"))?;
+                    let f32 = f32.ok_or_else(|| Error::missing_field("f32"))?;
+                    let f64 = f64.ok_or_else(|| Error::missing_field("f64"))?;
+                    let usize = usize.ok_or_else(|| Error::missing_field("usize"))?;
+                    let isize = isize.ok_or_else(|| Error::missing_field("isize"))?;
+                    let string = string.ok_or_else(|| Error::missing_field("string"))?;
 
                     Ok(PrimitiveDataTypesStruct {
-                        i8: i8,
-                        i16: i16,
-                        i32: i32,
-                        i64: i64,
-                        i128: i128,
-                        u8: u8,
-                        u16: u16,
-                        u32: u32,
-                        u64: u64,
-                        u128: u128,
-                        f32: f32,
-                        f64: f64,
-                        usize: usize,
-                        isize: isize,
-                        string: string,
+                        i8,
+                        i16,
+                        i32,
+                        i64,
+                        i128,
+                        u8,
+                        u16,
+                        u32,
+                        u64,
+                        u128,
+                        f32,
+                        f64,
+                        usize,
+                        isize,
+                        string,
                     })
                 }
             }
@@ -0,0 +1,166 @@
+//! Versioned documents, brought up to date before they are deserialized
+//!
+//! A struct annotated with `#[contra(version = 3, migrate("v1_to_v2", "v2_to_v3"))]` writes its version
+//! as [VERSION_FIELD] in front of its fields. When loading a document tagged with an older version,
+//! the rest of the document is captured as [Value], the [Migration]s leading from its version
+//! to the current one are applied in order and only then the fields are deserialized.
+//!
+//! The migrations are listed oldest first, the last one leads to the current version.
+//! Documents without a version are deserialized as they are, e.g. overrides from the environment
+//! or the command line.
+use std::collections::BTreeMap;
+
+use crate::{
+    deserialize::{Deserializer, MapAccess, Visitor},
+    error::{Error, SuccessResult},
+    value::{Number, Value},
+};
+
+/// Name of the field holding the version of a document
+pub const VERSION_FIELD: &str = "_version";
+
+/// Brings a document from one version to the next, e.g. by renaming or converting fields
+pub type Migration = fn(&mut Value) -> SuccessResult;
+
+/// The version of *document*, *None* if it carries none
+pub fn version(document: &Value) -> Result<Option<u32>, Error> {
+    let tag = match document.get(VERSION_FIELD) {
+        Some(tag) => tag,
+        None => return Ok(None),
+    };
+    // versions are written as numbers, which may be quoted
+    let version = match tag {
+        Value::String(tag) => tag.parse().ok(),
+        tag => tag.as_u64().and_then(|version| u32::try_from(version).ok()),
+    };
+    version.map(Some).ok_or_else(|| {
+        Error::invalid_value(format!("`{}` for `{}`", tag, VERSION_FIELD), "a version")
+    })
+}
+
+/// Applies the *migrations* leading from the version of *document* to *current* and removes the version
+///
+/// Documents that are not objects or carry no version are left untouched.
+///
+/// # Example
+/// ```
+/// use lib_contra::{error::SuccessResult, json, migrate::migrate, value::Value};
+///
+/// fn rename_hp(player: &mut Value) -> SuccessResult {
+///     if let Some(player) = player.as_object_mut() {
+///         if let Some(hp) = player.remove("hp") {
+///             player.insert("health".to_string(), hp);
+///         }
+///     }
+///     Ok(())
+/// }
+///
+/// let mut player = json!({ "_version": 1, "hp": 10 });
+/// migrate(&mut player, 2, &[rename_hp]).unwrap();
+/// assert_eq!(player, json!({ "health": 10 }));
+/// ```
+pub fn migrate(document: &mut Value, current: u32, migrations: &[Migration]) -> SuccessResult {
+    let found = match document.as_object() {
+        Some(_) => version(document)?,
+        None => None,
+    };
+    let found = match found {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+    let oldest = current.saturating_sub(migrations.len() as u32).max(1);
+    if found < oldest || found > current {
+        return Err(Error::invalid_value(
+            format!("version {}", found),
+            &format!("a version from {} to {}", oldest, current),
+        ));
+    }
+    if let Some(object) = document.as_object_mut() {
+        object.remove(VERSION_FIELD);
+    }
+    let skip = migrations.len() - (current - found) as usize;
+    for migration in migrations.iter().skip(skip) {
+        migration(document)?;
+    }
+    Ok(())
+}
+
+/// Captures the rest of a map whose version tag, *found*, was just read, migrates it and visits the result
+///
+/// Called by the derived [Deserialize](crate::deserialize::Deserialize) if *found* is not the *current* version.
+/// The tag must be the *first* entry, as the entries in front of it were already deserialized.
+pub fn migrate_map<'de, M: MapAccess<'de>, V: Visitor<'de>>(
+    mut map: M,
+    found: u32,
+    first: bool,
+    current: u32,
+    migrations: &[Migration],
+    visitor: V,
+) -> Result<V::Value, Error> {
+    if !first {
+        return Err(Error::invalid_value(
+            format!("`{}` after other fields", VERSION_FIELD),
+            &format!(
+                "`{}` as the first field of a version {} document",
+                VERSION_FIELD, found
+            ),
+        ));
+    }
+    let mut document = BTreeMap::new();
+    document.insert(
+        VERSION_FIELD.to_string(),
+        Value::Number(Number::Unsigned(found as u128)),
+    );
+    while let Some(key) = map.next_key::<String>()? {
+        let value = map.next_value()?;
+        document.insert(key, value);
+    }
+    let mut document = Value::Object(document);
+    migrate(&mut document, current, migrations)?;
+    document.deserialize_struct(visitor)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::json;
+
+    fn add_one(document: &mut Value) -> SuccessResult {
+        if let Some(steps) = document.as_object_mut() {
+            let n = steps.len();
+            steps.insert(format!("step{}", n), Value::Bool(true));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn only_missing_migrations_are_applied() {
+        let mut document = json!({ "_version": "2" });
+        migrate(&mut document, 3, &[add_one, add_one]).unwrap();
+        assert_eq!(document, json!({ "step0": true }));
+
+        let mut document = json!({ "_version": 1 });
+        migrate(&mut document, 3, &[add_one, add_one]).unwrap();
+        assert_eq!(document, json!({ "step0": true, "step1": true }));
+
+        let mut document = json!({ "hp": 1 });
+        migrate(&mut document, 3, &[add_one, add_one]).unwrap();
+        assert_eq!(document, json!({ "hp": 1 }));
+
+        let mut document = json!({ "_version": 3 });
+        migrate(&mut document, 3, &[add_one, add_one]).unwrap();
+        assert_eq!(document, json!({}));
+    }
+
+    #[test]
+    fn unsupported_versions_are_rejected() {
+        let mut document = json!({ "_version": 4 });
+        let err = migrate(&mut document, 3, &[add_one]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid value: found version 4, expected a version from 2 to 3"
+        );
+        assert!(migrate(&mut json!({ "_version": 1 }), 3, &[add_one]).is_err());
+        assert!(migrate(&mut json!({ "_version": "three" }), 3, &[]).is_err());
+    }
+}
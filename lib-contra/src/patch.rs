@@ -0,0 +1,464 @@
+//! [RFC 6902](https://www.rfc-editor.org/rfc/rfc6902) JSON Patch and [RFC 7386](https://www.rfc-editor.org/rfc/rfc7386) Merge Patch
+//!
+//! Both apply to [Value]s as well as to any type that is [Serialize] and [DeserializeOwned],
+//! the latter by a round trip through [Value].
+use std::collections::BTreeMap;
+
+use crate::{
+    deserialize::{Deserialize, DeserializeOwned, Deserializer},
+    diff::Change,
+    error::{Error, SuccessResult},
+    position::Position,
+    serialize::{Serialize, Serializer},
+    value::{from_value, pointer::Pointer, to_value, Value},
+};
+
+/// A single operation of a [Patch]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    /// Inserts into an array, or adds or replaces an object member
+    Add {
+        path: Pointer,
+        value: Value,
+    },
+    Remove {
+        path: Pointer,
+    },
+    Replace {
+        path: Pointer,
+        value: Value,
+    },
+    /// Removes the value at *from* and adds it at *path*
+    Move {
+        from: Pointer,
+        path: Pointer,
+    },
+    /// Adds a copy of the value at *from* at *path*
+    Copy {
+        from: Pointer,
+        path: Pointer,
+    },
+    /// Fails unless the value at *path* equals *value*
+    Test {
+        path: Pointer,
+        value: Value,
+    },
+}
+
+/// A sequence of operations applied as a whole: if any operation fails, the target is left untouched
+///
+/// # Example
+/// ```
+/// use lib_contra::{deserialize::json::FromJson, json, patch::Patch};
+///
+/// let patch = Patch::from_json(r#"[
+///     { "op": "test", "path": "/sword/damage", "value": 10 },
+///     { "op": "replace", "path": "/sword/damage", "value": 12 },
+///     { "op": "add", "path": "/axe", "value": { "damage": 15 } }
+/// ]"#).unwrap();
+///
+/// let mut balance = json!({ "sword": { "damage": 10 } });
+/// patch.apply(&mut balance).unwrap();
+/// assert_eq!(balance, json!({ "sword": { "damage": 12 }, "axe": { "damage": 15 } }));
+///
+/// // the test operation fails now, nothing is applied
+/// assert!(patch.apply(&mut balance).is_err());
+/// assert_eq!(balance["sword"]["damage"].as_u64(), Some(12));
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Patch {
+    operations: Vec<Operation>,
+}
+
+impl Patch {
+    pub fn new(operations: Vec<Operation>) -> Self {
+        Self { operations }
+    }
+
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    /// The patch turning the old into the new value of a [diff](crate::diff::diff)
+    pub fn from_changes(changes: Vec<Change>) -> Self {
+        let operations = changes
+            .into_iter()
+            .map(|change| match change {
+                Change::Added { path, value } => Operation::Add { path, value },
+                Change::Removed { path, .. } => Operation::Remove { path },
+                Change::Modified { path, new, .. } => Operation::Replace { path, value: new },
+            })
+            .collect();
+        Self { operations }
+    }
+
+    /// Applies all operations, *value* is only modified if all of them succeed
+    pub fn apply(&self, value: &mut Value) -> SuccessResult {
+        let mut patched = value.clone();
+        for (i, operation) in self.operations.iter().enumerate() {
+            operation
+                .apply(&mut patched)
+                .map_err(|err| Error::custom(format!("patch operation {} failed: {}", i, err)))?;
+        }
+        *value = patched;
+        Ok(())
+    }
+
+    /// Applies all operations to a typed value, *target* is only modified if all of them succeed
+    /// and the result deserializes back into *T*
+    pub fn apply_to<T: Serialize + DeserializeOwned>(&self, target: &mut T) -> SuccessResult {
+        let mut value = to_value(target)?;
+        self.apply(&mut value)?;
+        *target = from_value(value)?;
+        Ok(())
+    }
+}
+
+impl Operation {
+    fn apply(&self, value: &mut Value) -> SuccessResult {
+        match self {
+            Operation::Add { path, value: new } => add(value, path, new.clone()),
+            Operation::Remove { path } => remove(value, path).map(|_| ()),
+            Operation::Replace { path, value: new } => match path.get_mut(value) {
+                Some(old) => {
+                    *old = new.clone();
+                    Ok(())
+                }
+                None => Err(not_found(path)),
+            },
+            Operation::Move { from, path } => {
+                if path != from && path.starts_with(from) {
+                    return Err(Error::custom(format!(
+                        "cannot move `{}` into itself at `{}`",
+                        from, path
+                    )));
+                }
+                let moved = remove(value, from)?;
+                add(value, path, moved)
+            }
+            Operation::Copy { from, path } => {
+                let copied = from.get(value).cloned().ok_or_else(|| not_found(from))?;
+                add(value, path, copied)
+            }
+            Operation::Test {
+                path,
+                value: expected,
+            } => match path.get(value) {
+                Some(actual) if actual == expected => Ok(()),
+                Some(actual) => Err(Error::custom(format!(
+                    "test of `{}` failed, expected {} but found {}",
+                    path, expected, actual
+                ))),
+                None => Err(not_found(path)),
+            },
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Operation::Add { .. } => "add",
+            Operation::Remove { .. } => "remove",
+            Operation::Replace { .. } => "replace",
+            Operation::Move { .. } => "move",
+            Operation::Copy { .. } => "copy",
+            Operation::Test { .. } => "test",
+        }
+    }
+}
+
+fn add(value: &mut Value, path: &Pointer, new: Value) -> SuccessResult {
+    let (parent, last) = match (path.parent(), path.last()) {
+        (Some(parent), Some(last)) => (parent, last),
+        _ => {
+            *value = new;
+            return Ok(());
+        }
+    };
+    match parent.get_mut(value) {
+        Some(Value::Array(array)) => {
+            let index = match last {
+                "-" => array.len(),
+                token => crate::value::pointer::index(token)
+                    .filter(|index| *index <= array.len())
+                    .ok_or_else(|| not_found(path))?,
+            };
+            array.insert(index, new);
+            Ok(())
+        }
+        Some(Value::Object(object)) => {
+            object.insert(last.to_string(), new);
+            Ok(())
+        }
+        _ => Err(path.missing()),
+    }
+}
+
+fn remove(value: &mut Value, path: &Pointer) -> Result<Value, Error> {
+    match path.parent() {
+        Some(_) => path.remove(value).ok_or_else(|| not_found(path)),
+        None => Err(Error::custom("cannot remove the whole document")),
+    }
+}
+
+fn not_found(path: &Pointer) -> Error {
+    Error::custom(format!("`{}` does not exist", path))
+}
+
+/// Applies a merge patch: objects are merged recursively, `null` removes a member, anything else replaces
+///
+/// # Example
+/// ```
+/// use lib_contra::{json, patch::merge};
+///
+/// let mut settings = json!({ "volume": 3, "keys": { "jump": "space", "crouch": "c" } });
+/// merge(&mut settings, &json!({ "volume": 5, "keys": { "crouch": null } }));
+/// assert_eq!(settings, json!({ "volume": 5, "keys": { "jump": "space" } }));
+/// ```
+pub fn merge(target: &mut Value, patch: &Value) {
+    match patch {
+        Value::Object(patch) => {
+            if !matches!(target, Value::Object(_)) {
+                *target = Value::Object(BTreeMap::new());
+            }
+            if let Value::Object(target) = target {
+                for (key, patch) in patch.iter() {
+                    match patch {
+                        Value::Null => {
+                            target.remove(key);
+                        }
+                        patch => merge(target.entry(key.clone()).or_default(), patch),
+                    }
+                }
+            }
+        }
+        patch => *target = patch.clone(),
+    }
+}
+
+/// Applies a merge patch to a typed value, *target* is only modified if the result deserializes back into *T*
+pub fn merge_into<T: Serialize + DeserializeOwned>(target: &mut T, patch: &Value) -> SuccessResult {
+    let mut value = to_value(target)?;
+    merge(&mut value, patch);
+    *target = from_value(value)?;
+    Ok(())
+}
+
+// ##########################################
+
+impl Serialize for Operation {
+    fn serialize<S: Serializer>(&self, ser: &mut S, pos: &Position) -> SuccessResult {
+        let mut object = BTreeMap::new();
+        object.insert("op".to_string(), Value::String(self.name().to_string()));
+        let (path, from, value) = match self {
+            Operation::Add { path, value }
+            | Operation::Replace { path, value }
+            | Operation::Test { path, value } => (path, None, Some(value)),
+            Operation::Remove { path } => (path, None, None),
+            Operation::Move { from, path } | Operation::Copy { from, path } => {
+                (path, Some(from), None)
+            }
+        };
+        object.insert("path".to_string(), Value::String(path.to_string()));
+        if let Some(from) = from {
+            object.insert("from".to_string(), Value::String(from.to_string()));
+        }
+        if let Some(value) = value {
+            object.insert("value".to_string(), value.clone());
+        }
+        Value::Object(object).serialize(ser, pos)
+    }
+}
+
+impl<'de> Deserialize<'de> for Operation {
+    fn deserialize<D: Deserializer<'de>>(des: D) -> Result<Self, Error> {
+        let mut object = match Value::deserialize(des)? {
+            Value::Object(object) => object,
+            other => return Err(Error::invalid_type(other.kind(), "patch operation")),
+        };
+        let mut take =
+            |field: &'static str| object.remove(field).ok_or(Error::missing_field(field));
+        let pointer = |value: Value| match value {
+            Value::String(pointer) => Pointer::parse(&pointer),
+            other => Err(Error::invalid_type(other.kind(), "JSON pointer")),
+        };
+
+        let operation = match take("op")? {
+            Value::String(op) => op,
+            other => return Err(Error::invalid_type(other.kind(), "str")),
+        };
+        match operation.as_str() {
+            "add" => Ok(Operation::Add {
+                path: pointer(take("path")?)?,
+                value: take("value")?,
+            }),
+            "remove" => Ok(Operation::Remove {
+                path: pointer(take("path")?)?,
+            }),
+            "replace" => Ok(Operation::Replace {
+                path: pointer(take("path")?)?,
+                value: take("value")?,
+            }),
+            "move" => Ok(Operation::Move {
+                from: pointer(take("from")?)?,
+                path: pointer(take("path")?)?,
+            }),
+            "copy" => Ok(Operation::Copy {
+                from: pointer(take("from")?)?,
+                path: pointer(take("path")?)?,
+            }),
+            "test" => Ok(Operation::Test {
+                path: pointer(take("path")?)?,
+                value: take("value")?,
+            }),
+            other => Err(Error::invalid_value(
+                format!("`{}`", other),
+                "one of `add`, `remove`, `replace`, `move`, `copy`, `test`",
+            )),
+        }
+    }
+}
+
+impl Serialize for Patch {
+    fn serialize<S: Serializer>(&self, ser: &mut S, pos: &Position) -> SuccessResult {
+        self.operations.serialize(ser, pos)
+    }
+}
+
+impl<'de> Deserialize<'de> for Patch {
+    fn deserialize<D: Deserializer<'de>>(des: D) -> Result<Self, Error> {
+        Vec::deserialize(des).map(Patch::new)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{deserialize::json::FromJson, diff::diff_values, json, serialize::json::IntoJson};
+
+    fn patch(json: &str) -> Patch {
+        Patch::from_json(json).unwrap()
+    }
+
+    #[test]
+    fn add_inserts_into_arrays() {
+        let mut value = json!({ "list": [1, 3] });
+        patch(r#"[{ "op": "add", "path": "/list/1", "value": 2 }, { "op": "add", "path": "/list/-", "value": 4 }]"#)
+            .apply(&mut value)
+            .unwrap();
+        assert_eq!(value, json!({ "list": [1, 2, 3, 4] }));
+    }
+
+    #[test]
+    fn move_copy_and_remove() {
+        let mut value = json!({ "a": { "b": 1 }, "c": [] });
+        patch(
+            r#"[
+                { "op": "copy", "from": "/a/b", "path": "/c/0" },
+                { "op": "move", "from": "/a", "path": "/d" },
+                { "op": "remove", "path": "/c" }
+            ]"#,
+        )
+        .apply(&mut value)
+        .unwrap();
+        assert_eq!(value, json!({ "d": { "b": 1 } }));
+    }
+
+    #[test]
+    fn failing_operation_leaves_value_untouched() {
+        let mut value = json!({ "a": 1 });
+        let err = patch(
+            r#"[{ "op": "replace", "path": "/a", "value": 2 }, { "op": "remove", "path": "/b" }]"#,
+        )
+        .apply(&mut value)
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "patch operation 1 failed: `/b` does not exist"
+        );
+        assert_eq!(value, json!({ "a": 1 }));
+
+        let err = patch(r#"[{ "op": "move", "from": "/a", "path": "/a/b" }]"#)
+            .apply(&mut value)
+            .unwrap_err();
+        assert!(err.to_string().contains("into itself"));
+    }
+
+    #[test]
+    fn invalid_operations_are_rejected() {
+        let err = Patch::from_json(r#"[{ "op": "delete", "path": "/a" }]"#).unwrap_err();
+        assert!(matches!(err.kind(), Error::InvalidValue { .. }));
+
+        let err = Patch::from_json(r#"[{ "op": "add", "path": "/a" }]"#).unwrap_err();
+        assert!(matches!(err.kind(), Error::MissingField("value")));
+    }
+
+    #[test]
+    fn patch_round_trips_through_json() {
+        let expected = patch(
+            r#"[{ "op": "copy", "from": "/a", "path": "/b" }, { "op": "test", "path": "", "value": [] }]"#,
+        );
+        let json = expected.to_json().unwrap();
+        assert_eq!(Patch::from_json(&json).unwrap(), expected);
+    }
+
+    #[test]
+    fn patch_from_diff_reproduces_new_value() {
+        let old = json!({ "a": [1, 2, 3], "b": { "c": 1 }, "d": true });
+        let new = json!({ "a": [1, 5], "b": { "e": null }, "f": "x" });
+
+        let mut value = old.clone();
+        Patch::from_changes(diff_values(&old, &new))
+            .apply(&mut value)
+            .unwrap();
+        assert_eq!(value, new);
+    }
+
+    #[test]
+    fn merge_patch_rfc_examples() {
+        let cases = [
+            (
+                json!({ "a": "b" }),
+                json!({ "a": "c" }),
+                json!({ "a": "c" }),
+            ),
+            (json!({ "a": "b" }), json!({ "a": null }), json!({})),
+            (
+                json!({ "a": [{ "b": "c" }] }),
+                json!({ "a": [1] }),
+                json!({ "a": [1] }),
+            ),
+            (json!(["a", "b"]), json!({ "a": "c" }), json!({ "a": "c" })),
+            (
+                json!({ "e": null }),
+                json!({ "a": 1 }),
+                json!({ "e": null, "a": 1 }),
+            ),
+            (
+                json!({}),
+                json!({ "a": { "bb": { "ccc": null } } }),
+                json!({ "a": { "bb": {} } }),
+            ),
+        ];
+        for (mut target, patch, expected) in cases {
+            merge(&mut target, &patch);
+            assert_eq!(target, expected);
+        }
+    }
+
+    #[test]
+    fn typed_targets_round_trip() {
+        let mut stats: Vec<u32> = vec![1, 2];
+        patch(r#"[{ "op": "add", "path": "/0", "value": 0 }]"#)
+            .apply_to(&mut stats)
+            .unwrap();
+        assert_eq!(stats, vec![0, 1, 2]);
+
+        // the result is no longer a Vec<u32>, stats stays as it was
+        let result = patch(r#"[{ "op": "add", "path": "/0", "value": "x" }]"#).apply_to(&mut stats);
+        assert!(result.is_err());
+        assert_eq!(stats, vec![0, 1, 2]);
+
+        merge_into(&mut stats, &json!([7])).unwrap();
+        assert_eq!(stats, vec![7]);
+    }
+}
@@ -1,88 +1,231 @@
 //! Allow saving and loading to/from disk
+pub mod format;
+
 use std::{
-    fs::File,
-    io::{self, BufReader, Cursor, Read, Write},
-    path::Path,
-    str::from_utf8,
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
 use crate::{
-    deserialize::{
-        json::{FromJson, JsonDeserializer},
-        Deserialize,
-    },
-    error::{AnyError, IoResult},
-    serialize::{
-        json::{IntoJson, JsonSerializer, PrettyJsonFormatter},
-        Serialize,
-    },
+    deserialize::DeserializeOwned,
+    error::{Error, IoResult},
+    serialize::Serialize,
 };
 
 /// Allow saving and loading to/from disk
 ///
-/// Automatically implemented for types that implement both [Serialize] and [Deserialize]
-pub trait Persistent: Serialize + Deserialize {
-    fn save(&self, path: &str) -> Result<(), AnyError>;
-    fn load(path: &str) -> Result<Self, AnyError>;
-}
+/// Automatically implemented for types that implement both [Serialize] and [DeserializeOwned]
+pub trait Persistent: Serialize + DeserializeOwned {
+    /// Writes the value to *path* in the [format] selected by its extension
+    fn save(&self, path: impl AsRef<Path>) -> Result<(), Error>;
+    /// Reads a value from *path* in the [format] selected by its extension, or detected from its content
+    fn load(path: impl AsRef<Path>) -> Result<Self, Error>;
 
-fn serialize_with_default<S: Serialize>(value: &S) -> Result<Vec<u8>, AnyError> {
-    let mut buffer = Vec::with_capacity(128);
-    let mut ser = DefaultSerializer::new(PrettyJsonFormatter::new("\t".to_string()), &mut buffer);
-    value.serialize(&mut ser, &crate::position::Position::Closing)?;
-    Ok(buffer)
+    /// Like [save](Persistent::save), keeping older versions according to *options*
+    fn save_with(&self, path: impl AsRef<Path>, options: SaveOptions) -> Result<(), Error>;
+
+    /// Like [load](Persistent::load), trying the backups kept by [save_with](Persistent::save_with) in order
+    /// if the file cannot be loaded
+    ///
+    /// If no backup can be loaded either, the error of the file itself is returned.
+    fn load_with_fallback(path: impl AsRef<Path>) -> Result<(Self, LoadedFrom), Error>;
+
+    /// Like [load](Persistent::load), the default value if the file does not exist
+    ///
+    /// Any other failure, e.g. a file that does not parse, is still returned.
+    fn load_or_default(path: impl AsRef<Path>) -> Result<Self, Error>
+    where
+        Self: Default;
+
+    /// Like [load_or_default](Persistent::load_or_default), additionally saving the default value to *path*,
+    /// including any missing parent directories
+    fn load_or_create(path: impl AsRef<Path>) -> Result<Self, Error>
+    where
+        Self: Default;
 }
 
-fn deserialize_with_default<D: Deserialize>(value: &[u8]) -> Result<D, AnyError> {
-    let cursor = Cursor::new(value);
-    let mut des = DefaultDeserializer::new(cursor);
-    D::deserialize(&mut des)
+/// Options of [Persistent::save_with]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SaveOptions {
+    /// Number of previous versions kept as `save.json.1` (newest) up to `save.json.<backups>` (oldest)
+    pub backups: usize,
 }
 
-fn serialize_factory<S: Serialize>(value: &S, path: &Path) -> Result<Vec<u8>, AnyError> {
-    if let Some(ending) = path.extension() {
-        if ending == "json" {
-            return IntoJson::to_json(value).map(|json| json.into_bytes());
-        }
-    }
-    serialize_with_default(value)
+/// The file [Persistent::load_with_fallback] loaded from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadedFrom {
+    Primary,
+    /// The backup with the given number, e.g. 1 for `save.json.1`
+    Backup(usize),
 }
 
-fn deserializer_factory<D: Deserialize>(value: &[u8], path: &Path) -> Result<D, AnyError> {
-    if let Some(ending) = path.extension() {
-        if ending == "json" {
-            return FromJson::from_json(
-                &from_utf8(value).expect("failed to convert content to utf8"),
-            );
-        }
-    }
-    deserialize_with_default(value)
+fn serialize_factory<S: Serialize>(value: &S, path: &Path) -> Result<Vec<u8>, Error> {
+    format::global().serialize(value, path)
 }
 
-type DefaultSerializer<'w> = JsonSerializer<'w, Vec<u8>, PrettyJsonFormatter>;
-type DefaultDeserializer<'w> = JsonDeserializer<Cursor<&'w [u8]>>;
+pub(crate) fn deserializer_factory<D: DeserializeOwned>(
+    value: &[u8],
+    path: &Path,
+) -> Result<D, Error> {
+    format::global().deserialize(value, path)
+}
 
-impl<T: Sized + Serialize + Deserialize> Persistent for T {
-    fn save(&self, path: &str) -> Result<(), AnyError> {
-        let path = Path::new(path);
+impl<T: Sized + Serialize + DeserializeOwned> Persistent for T {
+    fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
         let buffer = serialize_factory(self, path)?;
         write_bytes_file(buffer.as_slice(), path).map_err(|e| e.into())
     }
 
-    fn load(path: &str) -> Result<Self, AnyError> {
-        let path = Path::new(path);
+    fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
         let content = read_bytes_file(path)?;
         deserializer_factory(content.as_slice(), path)
     }
+
+    fn save_with(&self, path: impl AsRef<Path>, options: SaveOptions) -> Result<(), Error> {
+        let path = path.as_ref();
+        let buffer = serialize_factory(self, path)?;
+        rotate_backups(path, options.backups)?;
+        write_bytes_file(buffer.as_slice(), path).map_err(|e| e.into())
+    }
+
+    fn load_with_fallback(path: impl AsRef<Path>) -> Result<(Self, LoadedFrom), Error> {
+        let path = path.as_ref();
+        let primary = match Self::load(path) {
+            Ok(value) => return Ok((value, LoadedFrom::Primary)),
+            Err(err) => err,
+        };
+        for n in 1.. {
+            let backup = backup_path(path, n);
+            if !backup.exists() {
+                break;
+            }
+            // the format is selected by the primary path, backups only differ by their suffix
+            let loaded = read_bytes_file(&backup)
+                .map_err(Error::from)
+                .and_then(|content| deserializer_factory(content.as_slice(), path));
+            if let Ok(value) = loaded {
+                return Ok((value, LoadedFrom::Backup(n)));
+            }
+        }
+        Err(primary)
+    }
+
+    fn load_or_default(path: impl AsRef<Path>) -> Result<Self, Error>
+    where
+        Self: Default,
+    {
+        match Self::load(path) {
+            Err(err) if is_not_found(&err) => Ok(Self::default()),
+            loaded => loaded,
+        }
+    }
+
+    fn load_or_create(path: impl AsRef<Path>) -> Result<Self, Error>
+    where
+        Self: Default,
+    {
+        let path = path.as_ref();
+        match Self::load(path) {
+            Err(err) if is_not_found(&err) => {
+                let value = Self::default();
+                if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                    fs::create_dir_all(dir)?;
+                }
+                value.save(path)?;
+                Ok(value)
+            }
+            loaded => loaded,
+        }
+    }
+}
+
+fn is_not_found(err: &Error) -> bool {
+    matches!(err.kind(), Error::Io(err) if err.kind() == io::ErrorKind::NotFound)
+}
+
+/// `save.json` becomes `save.json.<n>`
+fn backup_path(path: &Path, n: usize) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".{}", n));
+    PathBuf::from(backup)
+}
+
+/// Shifts every backup one number up, dropping the oldest, and copies the current file to the first backup
+///
+/// The current file is copied rather than renamed, so it stays in place until it is atomically replaced.
+fn rotate_backups(path: &Path, backups: usize) -> IoResult {
+    if backups == 0 || !path.exists() {
+        return Ok(());
+    }
+    for n in (1..backups).rev() {
+        let from = backup_path(path, n);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, n + 1))?;
+        }
+    }
+    let content = read_bytes_file(path)?;
+    write_bytes_file(content.as_slice(), &backup_path(path, 1))
+}
+
+/// Replaces the file at *path* atomically, a crash or failure leaves either the old or the new content
+///
+/// The bytes are written to a temporary file next to *path*, synced to disk and renamed over *path*,
+/// afterwards the directory is synced so the rename itself is durable.
+pub(crate) fn write_bytes_file(bytes: &[u8], path: &Path) -> IoResult {
+    let tmp = temp_path(path)?;
+    // a file that already exists belongs to someone else and must not be removed
+    let file = OpenOptions::new().write(true).create_new(true).open(&tmp)?;
+    let written = write_synced(file, bytes).and_then(|_| fs::rename(&tmp, path));
+    if let Err(err) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(err);
+    }
+    sync_dir(path)
+}
+
+/// A hidden sibling of *path*, unique per call
+fn temp_path(path: &Path) -> Result<PathBuf, io::Error> {
+    let name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("`{}` does not name a file", path.display()),
+        )
+    })?;
+    let mut tmp = OsString::from(".");
+    tmp.push(name);
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    tmp.push(format!(".{}.{}.tmp", process::id(), n));
+    Ok(path.with_file_name(tmp))
 }
 
-fn write_bytes_file(bytes: &[u8], path: &Path) -> IoResult {
-    let mut f = File::create(path)?;
-    f.write_all(bytes)?;
+fn write_synced(mut file: File, bytes: &[u8]) -> IoResult {
+    file.write_all(bytes)?;
+    file.sync_all()
+}
+
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> IoResult {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    File::open(dir)?.sync_all()
+}
+
+/// Directories cannot be opened for syncing on other platforms
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> IoResult {
     Ok(())
 }
 
-fn read_bytes_file(path: &Path) -> Result<Vec<u8>, io::Error> {
+pub(crate) fn read_bytes_file(path: &Path) -> Result<Vec<u8>, io::Error> {
     let file = File::open(path)?;
     let mut reader = BufReader::new(file);
     let mut buffer = Vec::new();
@@ -99,7 +242,8 @@ mod test {
         path::Path,
     };
 
-    use super::Persistent;
+    use super::{LoadedFrom, Persistent, SaveOptions};
+    use crate::{deserialize::json::FromJson, error::Error};
 
     struct FileLifetime {
         pub(crate) path: String,
@@ -110,11 +254,11 @@ mod test {
             let path = Path::new(&self.path);
             if path.exists() {
                 if !path.is_dir() {
-                    fs::remove_file(&path)
-                        .expect(format!("failed to delete file: {}", self.path).as_str());
+                    fs::remove_file(path)
+                        .unwrap_or_else(|_| panic!("failed to delete file: {}", self.path));
                 } else {
-                    fs::remove_dir_all(&path)
-                        .expect(format!("failed to delete directory: {}", self.path).as_str());
+                    fs::remove_dir_all(path)
+                        .unwrap_or_else(|_| panic!("failed to delete directory: {}", self.path));
                 }
             }
         }
@@ -137,4 +281,147 @@ mod test {
         assert!(loaded.is_ok());
         assert_eq!(data, loaded.unwrap());
     }
+
+    fn leftovers(dir: &str) -> Vec<String> {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|name| name.ends_with(".tmp"))
+            .collect()
+    }
+
+    #[test]
+    fn save_replaces_existing_file_without_leftovers() {
+        let dir = FileLifetime {
+            path: "save_replaces".to_string(),
+        };
+        fs::create_dir(&dir.path).unwrap();
+        let path = format!("{}/volume.json", dir.path);
+
+        5u8.save(&path).unwrap();
+        7u8.save(&path).unwrap();
+
+        assert_eq!(u8::load(&path).unwrap(), 7);
+        assert!(leftovers(&dir.path).is_empty());
+    }
+
+    #[test]
+    fn concurrent_saves_do_not_mix() {
+        let dir = FileLifetime {
+            path: "save_concurrent".to_string(),
+        };
+        fs::create_dir(&dir.path).unwrap();
+        let path = format!("{}/names.json", dir.path);
+
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    let names = vec![format!("thread {}", i); 100 * (i + 1)];
+                    for _ in 0..10 {
+                        names.save(&path).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let names = Vec::<String>::load(&path).unwrap();
+        let i: usize = names[0].trim_start_matches("thread ").parse().unwrap();
+        assert_eq!(names.len(), 100 * (i + 1));
+        assert!(names.iter().all(|name| *name == names[0]));
+        assert!(leftovers(&dir.path).is_empty());
+    }
+
+    #[test]
+    fn failed_save_keeps_old_content() {
+        let dir = FileLifetime {
+            path: "save_fails".to_string(),
+        };
+        // a non-empty directory cannot be replaced by a file
+        let path = format!("{}/volume.json", dir.path);
+        fs::create_dir_all(format!("{}/kept", path)).unwrap();
+
+        assert!(5u8.save(&path).is_err());
+        assert!(Path::new(&format!("{}/kept", path)).is_dir());
+        assert!(leftovers(&dir.path).is_empty());
+    }
+
+    #[test]
+    fn backups_are_rotated_and_used_as_fallback() {
+        let dir = FileLifetime {
+            path: "save_backups".to_string(),
+        };
+        fs::create_dir(&dir.path).unwrap();
+        let path = format!("{}/level.json", dir.path);
+        let options = SaveOptions { backups: 2 };
+        // backups are loaded by their primary path, their own extension is not a format
+        let backup = |n: usize| -> u8 {
+            u8::from_json(&fs::read_to_string(format!("{}.{}", path, n)).unwrap()).unwrap()
+        };
+
+        for level in 1..=4u8 {
+            level.save_with(&path, options.clone()).unwrap();
+        }
+        assert_eq!(backup(1), 3);
+        assert_eq!(backup(2), 2);
+        assert!(!Path::new(&format!("{}.3", path)).exists());
+
+        assert_eq!(
+            u8::load_with_fallback(&path).unwrap(),
+            (4, LoadedFrom::Primary)
+        );
+
+        fs::write(&path, "{ corrupted").unwrap();
+        fs::write(format!("{}.1", path), "").unwrap();
+        assert_eq!(
+            u8::load_with_fallback(&path).unwrap(),
+            (2, LoadedFrom::Backup(2))
+        );
+
+        fs::remove_file(format!("{}.2", path)).unwrap();
+        assert!(u8::load_with_fallback(&path).is_err());
+    }
+
+    #[test]
+    fn paths_and_failures_are_reported_as_errors() {
+        let dir = FileLifetime {
+            path: "save_errors".to_string(),
+        };
+        fs::create_dir(&dir.path).unwrap();
+        let path = Path::new(&dir.path).join("level.json");
+
+        3u8.save(&path).unwrap();
+        assert_eq!(u8::load(path.as_path()).unwrap(), 3);
+
+        fs::write(&path, [0xff, 0xfe]).unwrap();
+        assert!(matches!(u8::load(&path), Err(Error::Syntax(_))));
+
+        let unknown = Path::new(&dir.path).join("level.sav");
+        assert!(matches!(3u8.save(&unknown), Err(Error::UnknownFormat(_))));
+        assert!(!unknown.exists());
+    }
+
+    #[test]
+    fn load_or_default_only_falls_back_on_missing_files() {
+        let dir = FileLifetime {
+            path: "load_or_default".to_string(),
+        };
+        let path = Path::new(&dir.path).join("nested/volume.json");
+
+        assert_eq!(u8::load_or_default(&path).unwrap(), 0);
+        assert!(!path.exists());
+
+        assert_eq!(u8::load_or_create(&path).unwrap(), 0);
+        assert_eq!(u8::load(&path).unwrap(), 0);
+
+        5u8.save(&path).unwrap();
+        assert_eq!(u8::load_or_create(&path).unwrap(), 5);
+
+        fs::write(&path, "{ corrupted").unwrap();
+        assert!(u8::load_or_default(&path).is_err());
+        assert!(u8::load_or_create(&path).is_err());
+    }
 }
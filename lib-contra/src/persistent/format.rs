@@ -0,0 +1,361 @@
+//! Formats [Persistent](super::Persistent) saves and loads files in
+//!
+//! Registered formats exchange [Value]s, so any format that can represent a [Value] can be added,
+//! e.g. by third-party crates for `.ron` or `.toml` files.
+//! The built-in formats are written and read directly, see [Encoding].
+//! [Persistent](super::Persistent) uses the [global] registry, which initially knows [Json] and [Binary].
+use std::{
+    ffi::OsStr,
+    path::Path,
+    str::from_utf8,
+    sync::{Arc, OnceLock, PoisonError, RwLock, RwLockReadGuard},
+};
+
+use crate::{
+    deserialize::{
+        binary::from_bytes_with,
+        json::{FromJson, JsonDeserializer},
+        DeserializeOwned, SliceRead,
+    },
+    error::Error,
+    serialize::{
+        binary::{to_bytes_with, IntEncoding},
+        json::IntoJson,
+        Serialize,
+    },
+    value::{from_value, to_value, Value},
+};
+
+/// A file format
+pub trait Format: Send + Sync {
+    /// Name of the format, e.g. `json`
+    fn name(&self) -> &str;
+
+    /// Extensions without the leading dot, matched case-insensitively
+    fn extensions(&self) -> &[&str];
+
+    /// Whether *content* looks like this format, e.g. because it starts with the magic bytes of the format
+    fn sniff(&self, _content: &[u8]) -> bool {
+        false
+    }
+
+    /// How values are brought into this format
+    fn encoding(&self) -> Encoding {
+        Encoding::Value
+    }
+
+    fn encode(&self, value: &Value) -> Result<Vec<u8>, Error>;
+
+    fn decode(&self, content: &[u8]) -> Result<Value, Error>;
+}
+
+/// How a [Format] encodes values
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Through [Value], by [Format::encode] and [Format::decode]
+    Value,
+    /// Directly from and into the type, as [json](crate::serialize::json)
+    ///
+    /// Keeps the declaration order of fields and reports errors with their line, column and path.
+    Json,
+    /// Directly from and into the type, in the [binary](crate::serialize::binary) format
+    ///
+    /// Binary data is not self-describing and cannot be captured as [Value].
+    Binary(IntEncoding),
+}
+
+/// The built-in JSON format
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Json;
+
+impl Format for Json {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["json"]
+    }
+
+    fn sniff(&self, content: &[u8]) -> bool {
+        matches!(
+            content.iter().find(|b| !b.is_ascii_whitespace()),
+            Some(b'{' | b'[' | b'"')
+        )
+    }
+
+    fn encoding(&self) -> Encoding {
+        Encoding::Json
+    }
+
+    fn encode(&self, value: &Value) -> Result<Vec<u8>, Error> {
+        value.to_json().map(String::into_bytes)
+    }
+
+    fn decode(&self, content: &[u8]) -> Result<Value, Error> {
+        Value::from_json(from_utf8(content)?)
+    }
+}
+
+/// The built-in [binary](crate::serialize::binary) format, with varint integers
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Binary;
+
+impl Format for Binary {
+    fn name(&self) -> &str {
+        "binary"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["bin"]
+    }
+
+    fn encoding(&self) -> Encoding {
+        Encoding::Binary(IntEncoding::Varint)
+    }
+
+    fn encode(&self, value: &Value) -> Result<Vec<u8>, Error> {
+        to_bytes_with(value, IntEncoding::Varint)
+    }
+
+    fn decode(&self, _content: &[u8]) -> Result<Value, Error> {
+        Err(Error::invalid_type(
+            "binary data, which is not self-describing",
+            "value",
+        ))
+    }
+}
+
+/// Maps extensions and content to [Format]s
+///
+/// Formats registered later take precedence over earlier ones sharing an extension.
+///
+/// # Example
+/// ```
+/// use std::collections::HashMap;
+/// use lib_contra::{
+///     deserialize::json::FromJson, error::Error, json, serialize::json::IntoJson,
+///     persistent::format::{Format, FormatRegistry}, value::Value,
+/// };
+///
+/// /// JSON prefixed by a magic number
+/// struct Tagged;
+///
+/// impl Format for Tagged {
+///     fn name(&self) -> &str { "tagged" }
+///     fn extensions(&self) -> &[&str] { &["tag"] }
+///     fn sniff(&self, content: &[u8]) -> bool { content.starts_with(b"TAG") }
+///     fn encode(&self, value: &Value) -> Result<Vec<u8>, Error> {
+///         Ok([b"TAG".to_vec(), value.to_json()?.into_bytes()].concat())
+///     }
+///     fn decode(&self, content: &[u8]) -> Result<Value, Error> {
+///         Value::from_json(std::str::from_utf8(content.strip_prefix(b"TAG").unwrap_or(content))?)
+///     }
+/// }
+///
+/// let mut registry = FormatRegistry::default();
+/// registry.register(Tagged);
+///
+/// let bytes = registry.serialize(&json!({ "volume": 5 }), "audio.tag").unwrap();
+/// // the extension is wrong, the magic number is recognized
+/// let audio: HashMap<String, u8> = registry.deserialize(&bytes, "audio.json").unwrap();
+/// assert_eq!(audio["volume"], 5);
+/// ```
+#[derive(Clone)]
+pub struct FormatRegistry {
+    formats: Vec<Arc<dyn Format>>,
+}
+
+impl Default for FormatRegistry {
+    /// A registry knowing [Json] and [Binary]
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register(Json).register(Binary);
+        registry
+    }
+}
+
+impl FormatRegistry {
+    /// A registry without any format
+    pub fn empty() -> Self {
+        Self { formats: vec![] }
+    }
+
+    pub fn register(&mut self, format: impl Format + 'static) -> &mut Self {
+        self.formats.push(Arc::new(format));
+        self
+    }
+
+    /// The format selected by the extension of *path*
+    pub fn by_extension(&self, path: &Path) -> Option<&dyn Format> {
+        let ext = path.extension().and_then(OsStr::to_str)?;
+        self.formats
+            .iter()
+            .rev()
+            .find(|format| {
+                format
+                    .extensions()
+                    .iter()
+                    .any(|known| known.eq_ignore_ascii_case(ext))
+            })
+            .map(Arc::as_ref)
+    }
+
+    /// The format recognizing *content*
+    pub fn by_content(&self, content: &[u8]) -> Option<&dyn Format> {
+        self.formats
+            .iter()
+            .rev()
+            .find(|format| format.sniff(content))
+            .map(Arc::as_ref)
+    }
+
+    /// Serializes *value* in the format selected by the extension of *path*
+    pub fn serialize<T: Serialize>(
+        &self,
+        value: &T,
+        path: impl AsRef<Path>,
+    ) -> Result<Vec<u8>, Error> {
+        let path = path.as_ref();
+        let format = self
+            .by_extension(path)
+            .ok_or_else(|| Error::UnknownFormat(path.to_path_buf()))?;
+        match format.encoding() {
+            Encoding::Value => format.encode(&to_value(value)?),
+            Encoding::Json => value.to_json().map(String::into_bytes),
+            Encoding::Binary(ints) => to_bytes_with(value, ints),
+        }
+    }
+
+    /// Deserializes *content* in the format selected by the extension of *path*
+    ///
+    /// If the extension is missing or unknown, or the content does not parse in its format,
+    /// the format is detected from the content itself.
+    pub fn deserialize<T: DeserializeOwned>(
+        &self,
+        content: &[u8],
+        path: impl AsRef<Path>,
+    ) -> Result<T, Error> {
+        let path = path.as_ref();
+        let parsed = self
+            .by_extension(path)
+            .map(|format| decode(format, content));
+        match (parsed, self.by_content(content)) {
+            (Some(Ok(value)), _) => Ok(value),
+            (Some(Err(err)), Some(sniffed)) => decode(sniffed, content).map_err(|_| err),
+            (Some(Err(err)), None) => Err(err),
+            (None, Some(sniffed)) => decode(sniffed, content),
+            (None, None) => Err(Error::UnknownFormat(path.to_path_buf())),
+        }
+    }
+}
+
+fn decode<T: DeserializeOwned>(format: &dyn Format, content: &[u8]) -> Result<T, Error> {
+    match format.encoding() {
+        Encoding::Value => from_value(format.decode(content)?),
+        Encoding::Json => {
+            let json = from_utf8(content)?;
+            T::deserialize(&mut JsonDeserializer::new(SliceRead::new(json.as_bytes())))
+        }
+        Encoding::Binary(ints) => from_bytes_with(content, ints),
+    }
+}
+
+static GLOBAL: OnceLock<RwLock<FormatRegistry>> = OnceLock::new();
+
+fn global_lock() -> &'static RwLock<FormatRegistry> {
+    GLOBAL.get_or_init(|| RwLock::new(FormatRegistry::default()))
+}
+
+/// The registry used by [Persistent](super::Persistent)
+pub fn global() -> RwLockReadGuard<'static, FormatRegistry> {
+    global_lock().read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Adds *format* to the [global] registry
+pub fn register(format: impl Format + 'static) {
+    global_lock()
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .register(format);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::json;
+
+    /// Lines of `key=value`
+    struct Lines;
+
+    impl Format for Lines {
+        fn name(&self) -> &str {
+            "lines"
+        }
+
+        fn extensions(&self) -> &[&str] {
+            &["lines", "json"]
+        }
+
+        fn encode(&self, value: &Value) -> Result<Vec<u8>, Error> {
+            let Value::Object(object) = value else {
+                return Err(Error::invalid_type("value", "object"));
+            };
+            Ok(object
+                .iter()
+                .map(|(key, value)| format!("{}={}\n", key, value))
+                .collect::<String>()
+                .into_bytes())
+        }
+
+        fn decode(&self, content: &[u8]) -> Result<Value, Error> {
+            from_utf8(content)?
+                .lines()
+                .map(|line| match line.split_once('=') {
+                    Some((key, value)) => Ok((key.to_string(), Value::String(value.to_string()))),
+                    None => Err(Error::syntax(format!(
+                        "expected `key=value`, found `{}`",
+                        line
+                    ))),
+                })
+                .collect::<Result<_, _>>()
+                .map(Value::Object)
+        }
+    }
+
+    #[test]
+    fn later_formats_take_precedence() {
+        let mut registry = FormatRegistry::default();
+        assert_eq!(
+            registry.by_extension(Path::new("a.JSON")).unwrap().name(),
+            "json"
+        );
+        registry.register(Lines);
+        assert_eq!(
+            registry.by_extension(Path::new("a.json")).unwrap().name(),
+            "lines"
+        );
+        assert!(registry.by_extension(Path::new("a")).is_none());
+    }
+
+    #[test]
+    fn content_is_sniffed_if_extension_does_not_fit() {
+        let mut registry = FormatRegistry::default();
+        registry.register(Lines);
+
+        let json = b"{ \"volume\": \"5\" }";
+        let value: Value = registry.deserialize(json, "audio.lines").unwrap();
+        assert_eq!(value, json!({ "volume": "5" }));
+        let value: Value = registry.deserialize(json, "audio").unwrap();
+        assert_eq!(value, json!({ "volume": "5" }));
+
+        let err = registry
+            .deserialize::<Value>(b"volume", "audio.lines")
+            .unwrap_err();
+        assert!(matches!(err, Error::Syntax(_)));
+        let err = FormatRegistry::empty()
+            .deserialize::<Value>(json, "audio.json")
+            .unwrap_err();
+        assert!(matches!(err, Error::UnknownFormat(_)));
+    }
+}
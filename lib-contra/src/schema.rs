@@ -0,0 +1,218 @@
+//! [JSON Schema](https://json-schema.org) documents describing the serialized form of a type
+//!
+//! Editors such as VS Code use them for autocompletion and validation of config files.
+//! Numbers are accepted as JSON numbers as well as strings, since the [JSON serializer](crate::serialize::json) writes them quoted.
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use crate::{
+    error::SuccessResult, json, persistent::write_bytes_file, serialize::json::IntoJson,
+    value::Value,
+};
+
+/// The dialect written into the `$schema` keyword of [schema_for]
+pub const DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Describes the serialized form of a type
+///
+/// Can be derived with `#[derive(Schema)]`, doc comments become descriptions and
+/// fields marked `#[contra(default)]` are not required.
+///
+/// # Example
+/// ```
+/// use lib_contra::{json, schema::{describe, object, Schema}, value::Value};
+///
+/// struct Volume {
+///     level: u8,
+/// }
+///
+/// impl Schema for Volume {
+///     fn schema() -> Value {
+///         let properties = vec![("level", describe(u8::schema(), "From 0 to 10"))];
+///         object("Volume", properties, &["level"])
+///     }
+/// }
+///
+/// assert_eq!(Volume::schema()["properties"]["level"]["description"], json!("From 0 to 10"));
+/// ```
+pub trait Schema {
+    /// The schema of the type, without the `$schema` keyword
+    fn schema() -> Value;
+}
+
+/// The schema of *T* as a standalone document
+pub fn schema_for<T: Schema>() -> Value {
+    let mut schema = T::schema();
+    if let Value::Object(object) = &mut schema {
+        object.insert("$schema".to_string(), json!(DIALECT));
+    }
+    schema
+}
+
+/// Schema of an object with a fixed set of *properties*, unknown properties are rejected
+pub fn object(title: &str, properties: Vec<(&str, Value)>, required: &[&str]) -> Value {
+    let properties: BTreeMap<String, Value> = properties
+        .into_iter()
+        .map(|(name, schema)| (name.to_string(), schema))
+        .collect();
+    json!({
+        "title": title,
+        "type": "object",
+        "properties": (Value::Object(properties)),
+        "required": (required.to_vec()),
+        "additionalProperties": false
+    })
+}
+
+/// Schema of a string that is one of the given variant names, each paired with its (possibly empty) description
+pub fn variants(title: &str, variants: &[(&str, &str)]) -> Value {
+    let names: Vec<&str> = variants.iter().map(|(name, _)| *name).collect();
+    let mut schema = json!({ "title": title, "type": "string", "enum": names });
+    if variants
+        .iter()
+        .any(|(_, description)| !description.is_empty())
+    {
+        // not part of the standard, but shown by VS Code next to the completions
+        let descriptions: Vec<&str> = variants
+            .iter()
+            .map(|(_, description)| *description)
+            .collect();
+        if let Value::Object(object) = &mut schema {
+            object.insert("enumDescriptions".to_string(), json!(descriptions));
+        }
+    }
+    schema
+}
+
+/// Sets the `description` of *schema*, an empty *description* leaves it unchanged
+pub fn describe(mut schema: Value, description: &str) -> Value {
+    if let (Value::Object(object), false) = (&mut schema, description.is_empty()) {
+        object.insert("description".to_string(), json!(description));
+    }
+    schema
+}
+
+/// Allow saving the schema of a type to disk
+///
+/// Automatically implemented for types that implement [Schema]
+pub trait PersistentSchema: Schema {
+    /// Writes the [schema_for] the type as JSON to *path*
+    fn save_schema(path: impl AsRef<Path>) -> SuccessResult;
+}
+
+impl<T: Schema> PersistentSchema for T {
+    fn save_schema(path: impl AsRef<Path>) -> SuccessResult {
+        let json = schema_for::<T>().to_json()?;
+        write_bytes_file(json.as_bytes(), path.as_ref()).map_err(|e| e.into())
+    }
+}
+
+// ##########################################
+
+macro_rules! impl_schema_number {
+    ($type: ty, $json_type: literal, $pattern: literal) => {
+        impl Schema for $type {
+            fn schema() -> Value {
+                json!({ "type": [$json_type, "string"], "pattern": $pattern })
+            }
+        }
+    };
+}
+
+impl_schema_number!(i8, "integer", "^-?[0-9]+$");
+impl_schema_number!(i16, "integer", "^-?[0-9]+$");
+impl_schema_number!(i32, "integer", "^-?[0-9]+$");
+impl_schema_number!(i64, "integer", "^-?[0-9]+$");
+impl_schema_number!(i128, "integer", "^-?[0-9]+$");
+impl_schema_number!(isize, "integer", "^-?[0-9]+$");
+impl_schema_number!(u8, "integer", "^[0-9]+$");
+impl_schema_number!(u16, "integer", "^[0-9]+$");
+impl_schema_number!(u32, "integer", "^[0-9]+$");
+impl_schema_number!(u64, "integer", "^[0-9]+$");
+impl_schema_number!(u128, "integer", "^[0-9]+$");
+impl_schema_number!(usize, "integer", "^[0-9]+$");
+impl_schema_number!(f32, "number", "^-?[0-9]+(\\.[0-9]+)?([eE][+-]?[0-9]+)?$");
+impl_schema_number!(f64, "number", "^-?[0-9]+(\\.[0-9]+)?([eE][+-]?[0-9]+)?$");
+
+impl Schema for bool {
+    fn schema() -> Value {
+        json!({ "type": "boolean" })
+    }
+}
+
+impl Schema for String {
+    fn schema() -> Value {
+        json!({ "type": "string" })
+    }
+}
+
+impl Schema for &str {
+    fn schema() -> Value {
+        String::schema()
+    }
+}
+
+impl Schema for Cow<'_, str> {
+    fn schema() -> Value {
+        String::schema()
+    }
+}
+
+impl<I: Schema> Schema for Vec<I> {
+    fn schema() -> Value {
+        json!({ "type": "array", "items": (I::schema()) })
+    }
+}
+
+impl<K, V: Schema> Schema for HashMap<K, V> {
+    fn schema() -> Value {
+        json!({ "type": "object", "additionalProperties": (V::schema()) })
+    }
+}
+
+/// Any value is valid
+impl Schema for Value {
+    fn schema() -> Value {
+        json!({})
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn collections_nest_their_items() {
+        let schema = HashMap::<String, Vec<bool>>::schema();
+        assert_eq!(
+            schema,
+            json!({
+                "type": "object",
+                "additionalProperties": { "type": "array", "items": { "type": "boolean" } }
+            })
+        );
+    }
+
+    #[test]
+    fn variant_descriptions_are_only_written_if_present() {
+        let plain = variants("Mode", &[("Easy", ""), ("Hard", "")]);
+        assert!(plain.get("enumDescriptions").is_none());
+
+        let documented = variants("Mode", &[("Easy", "For beginners"), ("Hard", "")]);
+        assert_eq!(documented["enumDescriptions"], json!(["For beginners", ""]));
+    }
+
+    #[test]
+    fn schema_document_is_valid_json() {
+        let document = schema_for::<Vec<f32>>().to_json().unwrap();
+        let parsed = <Value as crate::deserialize::json::FromJson>::from_json(&document).unwrap();
+        assert_eq!(parsed["$schema"], json!(DIALECT));
+        assert_eq!(
+            parsed["items"]["pattern"],
+            json!("^-?[0-9]+(\\.[0-9]+)?([eE][+-]?[0-9]+)?$")
+        );
+    }
+}
@@ -1,5 +1,8 @@
+use std::borrow::Cow;
+
 use crate::{error::SuccessResult, position::Position};
 
+pub mod binary;
 pub mod json;
 
 /// Allows for the serialization of the implemented type
@@ -61,6 +64,7 @@ impl_serialize_primitive!(f32, serialize_f32);
 impl_serialize_primitive!(f64, serialize_f64);
 impl_serialize_primitive!(usize, serialize_usize);
 impl_serialize_primitive!(isize, serialize_isize);
+impl_serialize_primitive!(bool, serialize_bool);
 impl_serialize_primitive!(String, serialize_str);
 
 impl Serialize for &str {
@@ -69,6 +73,12 @@ impl Serialize for &str {
     }
 }
 
+impl Serialize for Cow<'_, str> {
+    fn serialize<S: Serializer>(&self, ser: &mut S, _pos: &Position) -> SuccessResult {
+        ser.serialize_str(self)
+    }
+}
+
 impl<Item: Serialize> Serialize for Vec<Item> {
     fn serialize<S: Serializer>(&self, ser: &mut S, _pos: &Position) -> SuccessResult {
         let len = self.len();
@@ -79,8 +89,8 @@ impl<Item: Serialize> Serialize for Vec<Item> {
         for (i, trailing_item) in iter.enumerate() {
             ser.serialize_item(i, trailing_item, &Position::Trailing)?;
         }
-        if closing_item.is_some() {
-            ser.serialize_item(self.len() - 1, closing_item.unwrap(), &Position::Closing)?;
+        if let Some(closing_item) = closing_item {
+            ser.serialize_item(len - 1, closing_item, &Position::Closing)?;
         }
 
         ser.end_collection(stringify!(Vec<Item>))?;
@@ -118,6 +128,7 @@ pub trait Serializer {
     fn serialize_value<V: Serialize>(&mut self, value: &V, pos: &Position) -> SuccessResult;
 
     fn serialize_str(&mut self, value: &str) -> SuccessResult;
+    fn serialize_null(&mut self) -> SuccessResult;
 
     decl_serialize_primitive!(i8, serialize_i8);
     decl_serialize_primitive!(i16, serialize_i16);
@@ -133,4 +144,5 @@ pub trait Serializer {
     decl_serialize_primitive!(f64, serialize_f64);
     decl_serialize_primitive!(usize, serialize_usize);
     decl_serialize_primitive!(isize, serialize_isize);
+    decl_serialize_primitive!(bool, serialize_bool);
 }
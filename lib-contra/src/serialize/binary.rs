@@ -0,0 +1,214 @@
+//! Compact binary format in the style of bincode
+//!
+//! - integers are little-endian, either fixed-width or varints, see [IntEncoding]
+//! - floats are little-endian IEEE 754, bools a single byte
+//! - strings and sequences are prefixed by their length
+//! - structs are their fields in declaration order, without names
+//!
+//! The format is not self-describing, data can only be read back into the type it was written from.
+use std::io;
+
+use crate::{
+    error::{Error, SuccessResult},
+    position::Position,
+    serialize::{Serialize, Serializer},
+};
+
+/// How integers wider than a byte are encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IntEncoding {
+    /// As many bytes as the type is wide
+    Fixed,
+    /// LEB128, small numbers take a single byte, signed numbers are zigzag encoded first
+    #[default]
+    Varint,
+}
+
+/// Serializes *value* with [varint](IntEncoding::Varint) integers
+///
+/// # Example
+/// ```
+/// use lib_contra::{deserialize::binary::from_bytes, serialize::binary::to_bytes};
+///
+/// let bytes = to_bytes(&vec![1u32, 300]).unwrap();
+/// assert_eq!(bytes, [2, 1, 0xac, 0x02]);
+/// assert_eq!(from_bytes::<Vec<u32>>(&bytes).unwrap(), vec![1, 300]);
+/// ```
+pub fn to_bytes<S: Serialize>(value: &S) -> Result<Vec<u8>, Error> {
+    to_bytes_with(value, IntEncoding::default())
+}
+
+pub fn to_bytes_with<S: Serialize>(value: &S, ints: IntEncoding) -> Result<Vec<u8>, Error> {
+    let mut buffer = Vec::with_capacity(64);
+    value.serialize(
+        &mut BinarySerializer::new(&mut buffer, ints),
+        &Position::Closing,
+    )?;
+    Ok(buffer)
+}
+
+pub struct BinarySerializer<'w, W: io::Write> {
+    write: &'w mut W,
+    ints: IntEncoding,
+}
+
+impl<'w, W: io::Write> BinarySerializer<'w, W> {
+    pub fn new(write: &'w mut W, ints: IntEncoding) -> Self {
+        Self { write, ints }
+    }
+
+    fn write_unsigned(&mut self, value: u128, fixed: &[u8]) -> SuccessResult {
+        match self.ints {
+            IntEncoding::Fixed => self.write.write_all(fixed)?,
+            IntEncoding::Varint => self.write.write_all(&varint(value))?,
+        }
+        Ok(())
+    }
+
+    fn write_signed(&mut self, value: i128, fixed: &[u8]) -> SuccessResult {
+        // zigzag maps small magnitudes of either sign to small numbers
+        let zigzag = ((value << 1) ^ (value >> 127)) as u128;
+        self.write_unsigned(zigzag, fixed)
+    }
+
+    fn write_len(&mut self, len: usize) -> SuccessResult {
+        self.write_unsigned(len as u128, &(len as u64).to_le_bytes())
+    }
+}
+
+fn varint(mut value: u128) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(3);
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        match value {
+            0 => {
+                bytes.push(byte);
+                return bytes;
+            }
+            _ => bytes.push(byte | 0x80),
+        }
+    }
+}
+
+macro_rules! impl_serialize_unsigned {
+    ($type: ident, $ser_func: ident) => {
+        fn $ser_func(&mut self, value: &$type) -> SuccessResult {
+            self.write_unsigned(*value as u128, &value.to_le_bytes())
+        }
+    };
+}
+
+macro_rules! impl_serialize_signed {
+    ($type: ident, $ser_func: ident) => {
+        fn $ser_func(&mut self, value: &$type) -> SuccessResult {
+            self.write_signed(*value as i128, &value.to_le_bytes())
+        }
+    };
+}
+
+macro_rules! impl_serialize_bytes {
+    ($type: ident, $ser_func: ident) => {
+        fn $ser_func(&mut self, value: &$type) -> SuccessResult {
+            self.write.write_all(&value.to_le_bytes())?;
+            Ok(())
+        }
+    };
+}
+
+impl<'w, W: io::Write> Serializer for BinarySerializer<'w, W> {
+    fn begin_struct(&mut self, _name: &str, _fields: usize) -> SuccessResult {
+        Ok(())
+    }
+
+    fn end_struct(&mut self, _name: &str) -> SuccessResult {
+        Ok(())
+    }
+
+    fn begin_collection(&mut self, _name: &str, size: usize) -> SuccessResult {
+        self.write_len(size)
+    }
+
+    fn end_collection(&mut self, _name: &str) -> SuccessResult {
+        Ok(())
+    }
+
+    fn serialize_item<V: Serialize>(
+        &mut self,
+        _i: usize,
+        item: &V,
+        pos: &Position,
+    ) -> SuccessResult {
+        item.serialize(self, pos)
+    }
+
+    fn serialize_field<V: Serialize>(
+        &mut self,
+        _identifier: &str,
+        value: &V,
+        pos: &Position,
+    ) -> SuccessResult {
+        value.serialize(self, pos)
+    }
+
+    fn serialize_value<V: Serialize>(&mut self, value: &V, pos: &Position) -> SuccessResult {
+        value.serialize(self, pos)
+    }
+
+    fn serialize_str(&mut self, value: &str) -> SuccessResult {
+        self.write_len(value.len())?;
+        self.write.write_all(value.as_bytes())?;
+        Ok(())
+    }
+
+    /// Null carries no data
+    fn serialize_null(&mut self) -> SuccessResult {
+        Ok(())
+    }
+
+    fn serialize_usize(&mut self, value: &usize) -> SuccessResult {
+        self.serialize_u64(&(*value as u64))
+    }
+
+    fn serialize_isize(&mut self, value: &isize) -> SuccessResult {
+        self.serialize_i64(&(*value as i64))
+    }
+
+    fn serialize_bool(&mut self, value: &bool) -> SuccessResult {
+        self.write.write_all(&[*value as u8])?;
+        Ok(())
+    }
+
+    impl_serialize_bytes!(u8, serialize_u8);
+    impl_serialize_bytes!(i8, serialize_i8);
+    impl_serialize_bytes!(f32, serialize_f32);
+    impl_serialize_bytes!(f64, serialize_f64);
+    impl_serialize_unsigned!(u16, serialize_u16);
+    impl_serialize_unsigned!(u32, serialize_u32);
+    impl_serialize_unsigned!(u64, serialize_u64);
+    impl_serialize_unsigned!(u128, serialize_u128);
+    impl_serialize_signed!(i16, serialize_i16);
+    impl_serialize_signed!(i32, serialize_i32);
+    impl_serialize_signed!(i64, serialize_i64);
+    impl_serialize_signed!(i128, serialize_i128);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn integers_are_fixed_or_varint() {
+        assert_eq!(to_bytes(&127u32).unwrap(), [0x7f]);
+        assert_eq!(to_bytes(&-1i32).unwrap(), [0x01]);
+        assert_eq!(to_bytes(&-65i64).unwrap(), [0x81, 0x01]);
+        assert_eq!(
+            to_bytes_with(&300u32, IntEncoding::Fixed).unwrap(),
+            [0x2c, 0x01, 0, 0]
+        );
+        assert_eq!(
+            to_bytes_with(&"hi", IntEncoding::Fixed).unwrap(),
+            [2, 0, 0, 0, 0, 0, 0, 0, b'h', b'i']
+        );
+    }
+}
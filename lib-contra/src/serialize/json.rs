@@ -1,26 +1,33 @@
 use std::io;
 use std::mem::size_of;
 
-use crate::error::{AnyError, IoResult, SuccessResult};
+use crate::error::{Error, IoResult, SuccessResult};
 use crate::formatter::WriteFormatter;
 
 use crate::position::Position;
 use crate::serialize::{Serialize, Serializer};
 
 pub trait IntoJson {
-    fn to_json(&self) -> Result<String, AnyError>;
+    fn to_json(&self) -> Result<String, Error>;
+
+    /// Writes the JSON directly into *write*, without building an intermediate [String]
+    fn to_writer<W: io::Write>(&self, write: &mut W) -> SuccessResult;
 }
 
 impl<S: Serialize> IntoJson for S {
-    fn to_json(&self) -> Result<String, AnyError> {
+    fn to_json(&self) -> Result<String, Error> {
         let mut buffer: Vec<u8> = Vec::with_capacity(size_of::<S>());
-        let formatter = PrettyJsonFormatter::new("\t".to_string());
-        let mut serializer = JsonSerializer::new(formatter, &mut buffer);
-
-        self.serialize(&mut serializer, &Position::Closing)?;
+        self.to_writer(&mut buffer)?;
 
         unsafe { Ok(String::from_utf8_unchecked(buffer)) }
     }
+
+    fn to_writer<W: io::Write>(&self, write: &mut W) -> SuccessResult {
+        let formatter = PrettyJsonFormatter::new("\t".to_string());
+        let mut serializer = JsonSerializer::new(formatter, write);
+
+        self.serialize(&mut serializer, &Position::Closing)
+    }
 }
 
 pub struct JsonSerializer<'w, W: io::Write, F: WriteFormatter<W>> {
@@ -65,9 +72,9 @@ impl<'w, W: io::Write, F: WriteFormatter<W>> Serializer for JsonSerializer<'w, W
         self.formatter.write_field_key(self.write, identifier)?;
         self.formatter
             .write_field_assignnment_operator(self.write)?;
-        value.serialize(self, &pos)?;
+        value.serialize(self, pos)?;
         self.formatter
-            .write_field_assignnment_end(self.write, &pos)?;
+            .write_field_assignnment_end(self.write, pos)?;
         Ok(())
     }
 
@@ -97,7 +104,15 @@ impl<'w, W: io::Write, F: WriteFormatter<W>> Serializer for JsonSerializer<'w, W
         item: &V,
         pos: &Position,
     ) -> SuccessResult {
-        self.serialize_value(item, pos)
+        self.formatter.write_item_begin(self.write)?;
+        self.serialize_value(item, pos)?;
+        self.formatter.write_item_end(self.write, pos)?;
+        Ok(())
+    }
+
+    fn serialize_null(&mut self) -> SuccessResult {
+        self.formatter.write_null(self.write)?;
+        Ok(())
     }
 
     impl_serialize_primitive!(i8, serialize_i8, write_i8);
@@ -115,6 +130,7 @@ impl<'w, W: io::Write, F: WriteFormatter<W>> Serializer for JsonSerializer<'w, W
     impl_serialize_primitive!(usize, serialize_usize, write_usize);
     impl_serialize_primitive!(isize, serialize_isize, write_isize);
     impl_serialize_primitive!(str, serialize_str, write_str);
+    impl_serialize_primitive!(bool, serialize_bool, write_bool);
 }
 
 pub struct PrettyJsonFormatter {
@@ -148,8 +164,29 @@ impl PrettyJsonFormatter {
         Ok(())
     }
 
+    /// Writes *value* quoted, escaping quotes, backslashes and control characters
     fn write_escaped_string<W: io::Write>(&mut self, write: &mut W, value: &str) -> IoResult {
-        write.write_fmt(format_args!("\"{}\"", value))?;
+        write.write_all(b"\"")?;
+        let mut start = 0;
+        for (i, char) in value.bytes().enumerate() {
+            let escaped: &[u8] = match char {
+                b'"' => b"\\\"",
+                b'\\' => b"\\\\",
+                b'\n' => b"\\n",
+                b'\r' => b"\\r",
+                b'\t' => b"\\t",
+                0x00..=0x1F => b"",
+                _ => continue,
+            };
+            write.write_all(&value.as_bytes()[start..i])?;
+            match escaped.is_empty() {
+                true => write.write_fmt(format_args!("\\u{:04x}", char))?,
+                false => write.write_all(escaped)?,
+            }
+            start = i + 1;
+        }
+        write.write_all(&value.as_bytes()[start..])?;
+        write.write_all(b"\"")?;
         Ok(())
     }
 
@@ -181,7 +218,6 @@ macro_rules! impl_write_primitive {
 
 impl<W: io::Write> WriteFormatter<W> for PrettyJsonFormatter {
     fn write_struct_begin(&mut self, write: &mut W, _name: &str, _fields: usize) -> IoResult {
-        self.write_ident(write)?;
         self.write_unescaped_string(write, "{")?;
         self.write_line_break(write)?;
         self.increase_ident();
@@ -224,14 +260,31 @@ impl<W: io::Write> WriteFormatter<W> for PrettyJsonFormatter {
     fn write_collection_begin(&mut self, write: &mut W, _name: &str, _size: usize) -> IoResult {
         write.write_all(b"[")?;
         self.write_line_break(write)?;
+        self.increase_ident();
         Ok(())
     }
 
     fn write_collection_end(&mut self, write: &mut W, _name: &str) -> IoResult {
+        self.decrease_ident();
+        self.write_ident(write)?;
         write.write_all(b"]")?;
         Ok(())
     }
 
+    fn write_item_begin(&mut self, write: &mut W) -> IoResult {
+        self.write_ident(write)?;
+        Ok(())
+    }
+
+    fn write_item_end(&mut self, write: &mut W, pos: &Position) -> IoResult {
+        match pos {
+            Position::Trailing => self.write_seperator(write)?,
+            Position::Closing => (),
+        }
+        self.write_line_break(write)?;
+        Ok(())
+    }
+
     impl_write_primitive!(i8, write_i8);
     impl_write_primitive!(i16, write_i16);
     impl_write_primitive!(i32, write_i32);
@@ -246,5 +299,49 @@ impl<W: io::Write> WriteFormatter<W> for PrettyJsonFormatter {
     impl_write_primitive!(f64, write_f64);
     impl_write_primitive!(usize, write_usize);
     impl_write_primitive!(isize, write_isize);
-    impl_write_primitive!(str, write_str);
+
+    fn write_str(&mut self, write: &mut W, value: &str) -> IoResult {
+        self.write_escaped_string(write, value)
+    }
+
+    fn write_bool(&mut self, write: &mut W, value: &bool) -> IoResult {
+        self.write_unescaped_string(write, if *value { "true" } else { "false" })
+    }
+
+    fn write_null(&mut self, write: &mut W) -> IoResult {
+        self.write_unescaped_string(write, "null")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn collections_are_separated_and_indented() {
+        let json = vec![vec![1u8, 2], vec![]].to_json().unwrap();
+
+        assert_eq!(json, "[\n\t[\n\t\t\"1\",\n\t\t\"2\"\n\t],\n\t[\n\t]\n]");
+    }
+
+    #[test]
+    fn bool_and_null_are_unquoted() {
+        let json = vec![crate::value::Value::Bool(true), crate::value::Value::Null]
+            .to_json()
+            .unwrap();
+
+        assert_eq!(json, "[\n\ttrue,\n\tnull\n]");
+    }
+
+    #[test]
+    fn structs_begin_where_they_are_placed() {
+        let json = crate::json!({ "a": { "b": "x" }, "list": [{ "c": true }, []] })
+            .to_json()
+            .unwrap();
+
+        assert_eq!(
+            json,
+            "{\n\t\"a\" : {\n\t\t\"b\" : \"x\"\n\t},\n\t\"list\" : [\n\t\t{\n\t\t\t\"c\" : true\n\t\t},\n\t\t[\n\t\t]\n\t]\n}"
+        );
+    }
 }
@@ -0,0 +1,332 @@
+//! Domain constraints checked after deserialization
+//!
+//! The derived *Deserialize* implements [Validate] for structs with `#[contra(validate(...))]` fields
+//! and checks the constraints as soon as the struct is built:
+//! - `range(min = 0, max = 1)`: numbers, either bound may be omitted
+//! - `length(min = 1, max = 16)`: number of characters of strings, elements of collections
+//! - `regex = "^[a-z]+$"`: strings, requires the `regex` feature
+//! - `custom = "path::to::fn"`: any `fn(&T) -> Result<(), E>` where *E* is [Display]
+//!
+//! Validation is shallow: the constraints of a struct only cover its own fields,
+//! the [path](Error::Validation) of a violation is the name of the field.
+//! A nested struct checks its own constraints when it is deserialized, where the error is located
+//! at the full path, but [Validate::validate] of the outer struct does not descend into it.
+#[cfg(feature = "regex")]
+use std::sync::OnceLock;
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::HashMap,
+    fmt::{self, Display},
+};
+
+use crate::error::{Error, SuccessResult};
+
+/// Checks the constraints of a value, e.g. after it was edited in memory
+///
+/// # Example
+/// ```
+/// use lib_contra::{error::{Error, SuccessResult}, validate::{self, Bound, Validate}};
+///
+/// struct Audio {
+///     volume: f32,
+/// }
+///
+/// impl Validate for Audio {
+///     fn validate_each(&self, report: &mut dyn FnMut(Error) -> SuccessResult) -> SuccessResult {
+///         if let Err(reason) = validate::range(&self.volume, Some(Bound::Integer(0)), Some(Bound::Integer(1))) {
+///             report(Error::validation("volume", reason))?;
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let mut audio = Audio { volume: 0.5 };
+/// assert!(audio.validate().is_ok());
+///
+/// audio.volume = 1.5;
+/// assert_eq!(audio.validate().unwrap_err().to_string(), "invalid `volume`: must be at most 1, found 1.5");
+/// ```
+pub trait Validate {
+    /// Passes every violation to *report*, stops as soon as *report* returns an error
+    fn validate_each(&self, report: &mut dyn FnMut(Error) -> SuccessResult) -> SuccessResult;
+
+    /// The first violation, if any
+    fn validate(&self) -> SuccessResult {
+        self.validate_each(&mut Err)
+    }
+
+    /// All violations
+    fn violations(&self) -> Vec<Error> {
+        let mut violations = vec![];
+        let _ = self.validate_each(&mut |err| {
+            violations.push(err);
+            Ok(())
+        });
+        violations
+    }
+}
+
+/// A bound of [range], as written in the constraint
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bound {
+    Integer(i128),
+    Float(f64),
+}
+
+impl Display for Bound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bound::Integer(bound) => bound.fmt(f),
+            Bound::Float(bound) => bound.fmt(f),
+        }
+    }
+}
+
+/// Numbers that can be checked by [range]
+pub trait Numeric {
+    /// How the number compares to *bound*, *None* if it is not a number
+    ///
+    /// Integers are compared exactly to integer bounds, not via [f64].
+    fn compare(&self, bound: &Bound) -> Option<Ordering>;
+}
+
+macro_rules! impl_numeric_integer {
+    ($($type: ty),*) => {
+        $(impl Numeric for $type {
+            fn compare(&self, bound: &Bound) -> Option<Ordering> {
+                match bound {
+                    // only fails above i128::MAX, which exceeds every bound
+                    Bound::Integer(bound) => match i128::try_from(*self) {
+                        Ok(value) => Some(value.cmp(bound)),
+                        Err(_) => Some(Ordering::Greater),
+                    },
+                    Bound::Float(bound) => (*self as f64).partial_cmp(bound),
+                }
+            }
+        })*
+    };
+}
+
+macro_rules! impl_numeric_float {
+    ($($type: ty),*) => {
+        $(impl Numeric for $type {
+            fn compare(&self, bound: &Bound) -> Option<Ordering> {
+                match bound {
+                    Bound::Integer(bound) => (*self as f64).partial_cmp(&(*bound as f64)),
+                    Bound::Float(bound) => (*self as f64).partial_cmp(bound),
+                }
+            }
+        })*
+    };
+}
+
+impl_numeric_integer!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+impl_numeric_float!(f32, f64);
+
+/// Values whose size can be checked by [length]
+pub trait Length {
+    fn length(&self) -> usize;
+}
+
+impl Length for str {
+    fn length(&self) -> usize {
+        self.chars().count()
+    }
+}
+
+impl Length for String {
+    fn length(&self) -> usize {
+        self.as_str().length()
+    }
+}
+
+impl Length for &str {
+    fn length(&self) -> usize {
+        (*self).length()
+    }
+}
+
+impl Length for Cow<'_, str> {
+    fn length(&self) -> usize {
+        self.as_ref().length()
+    }
+}
+
+impl<I> Length for Vec<I> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+impl<K, V> Length for HashMap<K, V> {
+    fn length(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Checks that *value* lies within *min* and *max*, both inclusive
+pub fn range<N: Numeric + Display>(
+    value: &N,
+    min: Option<Bound>,
+    max: Option<Bound>,
+) -> Result<(), String> {
+    // NaN is not even within no bounds
+    if value.compare(&Bound::Integer(0)).is_none() {
+        return Err(format!("must be a number, found {}", value));
+    }
+    match (min, max) {
+        (Some(min), _) if value.compare(&min) == Some(Ordering::Less) => {
+            Err(format!("must be at least {}, found {}", min, value))
+        }
+        (_, Some(max)) if value.compare(&max) == Some(Ordering::Greater) => {
+            Err(format!("must be at most {}, found {}", max, value))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks that the length of *value* lies within *min* and *max*, both inclusive
+pub fn length<L: Length + ?Sized>(
+    value: &L,
+    min: Option<usize>,
+    max: Option<usize>,
+) -> Result<(), String> {
+    let len = value.length();
+    match (min, max) {
+        (Some(min), _) if len < min => {
+            Err(format!("length must be at least {}, found {}", min, len))
+        }
+        (_, Some(max)) if len > max => {
+            Err(format!("length must be at most {}, found {}", max, len))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks that *value* matches *pattern*
+///
+/// The pattern is compiled on every call, the derived constraints use a [Pattern] instead.
+#[cfg(feature = "regex")]
+pub fn regex(value: &str, pattern: &str) -> Result<(), String> {
+    let regex = compile(pattern)?;
+    matches(&regex, pattern, value)
+}
+
+#[cfg(not(feature = "regex"))]
+#[deprecated(note = "patterns can only be checked with the `regex` feature of contra")]
+pub fn regex(_value: &str, pattern: &str) -> Result<(), String> {
+    Err(missing_regex_feature(pattern))
+}
+
+/// A pattern compiled on its first check, emitted as a `static` for each `regex = "..."` constraint
+pub struct Pattern {
+    pattern: &'static str,
+    #[cfg(feature = "regex")]
+    compiled: OnceLock<Result<regex::Regex, String>>,
+}
+
+impl Pattern {
+    #[cfg(feature = "regex")]
+    pub const fn new(pattern: &'static str) -> Self {
+        Self {
+            pattern,
+            compiled: OnceLock::new(),
+        }
+    }
+
+    /// Fails to compile wherever a `static` pattern is declared, as the `regex` feature is disabled
+    #[cfg(not(feature = "regex"))]
+    pub const fn new(_pattern: &'static str) -> Self {
+        panic!("`regex = \"...\"` constraints require the `regex` feature of contra")
+    }
+
+    /// Checks that *value* matches the pattern
+    pub fn check(&self, value: &str) -> Result<(), String> {
+        #[cfg(feature = "regex")]
+        {
+            match self.compiled.get_or_init(|| compile(self.pattern)) {
+                Ok(regex) => matches(regex, self.pattern, value),
+                Err(err) => Err(err.clone()),
+            }
+        }
+        #[cfg(not(feature = "regex"))]
+        {
+            let _ = value;
+            Err(missing_regex_feature(self.pattern))
+        }
+    }
+}
+
+#[cfg(feature = "regex")]
+fn compile(pattern: &str) -> Result<regex::Regex, String> {
+    regex::Regex::new(pattern).map_err(|err| format!("invalid pattern `{}`: {}", pattern, err))
+}
+
+#[cfg(feature = "regex")]
+fn matches(regex: &regex::Regex, pattern: &str, value: &str) -> Result<(), String> {
+    match regex.is_match(value) {
+        true => Ok(()),
+        false => Err(format!("must match `{}`, found \"{}\"", pattern, value)),
+    }
+}
+
+#[cfg(not(feature = "regex"))]
+fn missing_regex_feature(pattern: &str) -> String {
+    format!(
+        "cannot check `{}`, the `regex` feature of contra is disabled",
+        pattern
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn range_checks_both_bounds() {
+        assert!(range(&5u8, Some(Bound::Integer(0)), Some(Bound::Float(10.0))).is_ok());
+        assert!(range(&-1i32, None, Some(Bound::Integer(10))).is_ok());
+        assert_eq!(
+            range(&0i32, Some(Bound::Float(0.5)), None).unwrap_err(),
+            "must be at least 0.5, found 0"
+        );
+        assert!(range(&0.5f32, Some(Bound::Integer(0)), Some(Bound::Integer(1))).is_ok());
+        assert!(range(&f32::NAN, None, None).is_err());
+    }
+
+    #[test]
+    fn range_compares_integers_exactly() {
+        let max = Some(Bound::Integer(u64::MAX as i128 - 1));
+        assert!(range(&(u64::MAX - 1), None, max).is_ok());
+        assert_eq!(
+            range(&u64::MAX, None, max).unwrap_err(),
+            "must be at most 18446744073709551614, found 18446744073709551615"
+        );
+        let min = Some(Bound::Integer(i64::MIN as i128 + 1));
+        assert!(range(&i64::MIN, min, None).is_err());
+        assert!(range(&u128::MAX, None, Some(Bound::Integer(i128::MAX))).is_err());
+    }
+
+    #[test]
+    fn length_counts_characters() {
+        assert!(length("äöü", None, Some(3)).is_ok());
+        assert_eq!(
+            length(&vec![1, 2, 3], Some(1), Some(2)).unwrap_err(),
+            "length must be at most 2, found 3"
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn regex_matches_pattern() {
+        assert!(regex("hero_1", "^[a-z_0-9]+$").is_ok());
+        assert!(regex("Hero", "^[a-z_0-9]+$").is_err());
+        assert!(regex("x", "(").unwrap_err().starts_with("invalid pattern"));
+
+        static PATTERN: Pattern = Pattern::new("^[a-z]+$");
+        assert!(PATTERN.check("hero").is_ok());
+        assert!(PATTERN.check("Hero").is_err());
+        assert!(PATTERN.compiled.get().is_some());
+    }
+}
@@ -0,0 +1,751 @@
+//! Dynamically typed data for content whose shape is not known at compile time
+pub mod pointer;
+
+use std::{
+    collections::{btree_map, BTreeMap},
+    fmt::{self, Display},
+    ops::Index,
+    vec,
+};
+
+use crate::{
+    deserialize::{Deserialize, DeserializeOwned, Deserializer, MapAccess, SeqAccess, Visitor},
+    error::{Error, SuccessResult},
+    position::Position,
+    serialize::{Serialize, Serializer},
+};
+
+/// Any value that can be represented in a self-describing format such as JSON
+///
+/// Like the JSON deserializer, the accessors and [from_value] accept numbers and booleans written as strings,
+/// e.g. `"42"` for a `u32`.
+///
+/// # Example
+/// ```
+/// use lib_contra::{deserialize::json::FromJson, value::Value};
+///
+/// let value = Value::from_json("{ \"items\": [{ \"slot\": 3 }] }").unwrap();
+/// assert_eq!(value["items"][0]["slot"].as_u64(), Some(3));
+/// assert!(value["missing"][7].is_null());
+/// ```
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Value {
+    #[default]
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    Array(Vec<Value>),
+    Object(BTreeMap<String, Value>),
+}
+
+/// A number of a [Value], keeps integers exact up to 128 bits
+///
+/// Numbers compare by their numeric value, `Unsigned(1)` equals `Signed(1)`.
+#[derive(Debug, Clone, Copy)]
+pub enum Number {
+    Unsigned(u128),
+    Signed(i128),
+    Float(f64),
+}
+
+static NULL: Value = Value::Null;
+
+impl Value {
+    /// Name of the variant, as used in error messages
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "bool",
+            Value::Number(_) => "number",
+            Value::String(_) => "str",
+            Value::Array(_) => "seq",
+            Value::Object(_) => "map",
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// The boolean, also if written as a string such as `"true"`, `"FALSE"`, `"1"` or `"0"`
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(bool) => Some(*bool),
+            Value::String(str) => parse_bool(str),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        self.parse()
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        self.parse()
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(Number::Float(float)) => Some(*float),
+            _ => self.parse(),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(str) => Some(str),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    pub fn as_array_mut(&mut self) -> Option<&mut Vec<Value>> {
+        match self {
+            Value::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&BTreeMap<String, Value>> {
+        match self {
+            Value::Object(object) => Some(object),
+            _ => None,
+        }
+    }
+
+    pub fn as_object_mut(&mut self) -> Option<&mut BTreeMap<String, Value>> {
+        match self {
+            Value::Object(object) => Some(object),
+            _ => None,
+        }
+    }
+
+    /// The value of *key* if this is an object containing it
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_object().and_then(|object| object.get(key))
+    }
+
+    fn parse<N: std::str::FromStr>(&self) -> Option<N> {
+        match self {
+            Value::Number(number) => number.to_string().parse().ok(),
+            Value::String(str) => str.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Yields [Value::Null] for missing keys and for anything that is not an object
+impl Index<&str> for Value {
+    type Output = Value;
+
+    fn index(&self, key: &str) -> &Self::Output {
+        self.get(key).unwrap_or(&NULL)
+    }
+}
+
+/// Yields [Value::Null] for indices out of bounds and for anything that is not an array
+impl Index<usize> for Value {
+    type Output = Value;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        self.as_array()
+            .and_then(|array| array.get(index))
+            .unwrap_or(&NULL)
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        match (*self, *other) {
+            (Number::Unsigned(a), Number::Unsigned(b)) => a == b,
+            (Number::Signed(a), Number::Signed(b)) => a == b,
+            (Number::Unsigned(a), Number::Signed(b)) | (Number::Signed(b), Number::Unsigned(a)) => {
+                u128::try_from(b) == Ok(a)
+            }
+            (Number::Float(a), b) | (b, Number::Float(a)) => match b {
+                Number::Unsigned(b) => a == b as f64,
+                Number::Signed(b) => a == b as f64,
+                Number::Float(b) => a == b,
+            },
+        }
+    }
+}
+
+/// Compact JSON, numbers are written unquoted
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(bool) => write!(f, "{}", bool),
+            Value::Number(number) => write!(f, "{}", number),
+            Value::String(str) => write_quoted(f, str),
+            Value::Array(array) => {
+                write!(f, "[")?;
+                for (i, item) in array.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Value::Object(object) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in object.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_quoted(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_quoted(f: &mut fmt::Formatter<'_>, str: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for char in str.chars() {
+        match char {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            char if (char as u32) < 0x20 => write!(f, "\\u{:04x}", char as u32)?,
+            char => write!(f, "{}", char)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Number::Unsigned(number) => write!(f, "{}", number),
+            Number::Signed(number) => write!(f, "{}", number),
+            Number::Float(number) => write!(f, "{}", number),
+        }
+    }
+}
+
+/// Converts any [Serialize] into a [Value]
+pub fn to_value<T: Serialize>(value: &T) -> Result<Value, Error> {
+    let mut ser = ValueSerializer { value: Value::Null };
+    value.serialize(&mut ser, &Position::Closing)?;
+    Ok(ser.value)
+}
+
+/// Converts a [Value] into any [Deserialize]
+///
+/// # Example
+/// ```
+/// use lib_contra::value::{from_value, Value};
+///
+/// let value = Value::Array(vec![Value::String("1".to_string()), Value::String("2".to_string())]);
+/// let numbers: Vec<u8> = from_value(value).unwrap();
+/// assert_eq!(numbers, vec![1, 2]);
+/// ```
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, Error> {
+    T::deserialize(value)
+}
+
+/// Builds a [Value] from JSON-like syntax
+///
+/// Any expression whose type implements [Serialize] can be interpolated, it is converted via [to_value].
+/// Object keys are string literals or parenthesized expressions.
+/// The braces of an object may also delimit the macro, as in `json!{ "key": value }`.
+///
+/// # Example
+/// ```
+/// use lib_contra::{json, value::Value};
+///
+/// let slots = vec![1u8, 2];
+/// let value = json!({ "name": "Hero", "alive": true, "pet": null, "slots": [0, slots, { "x": -1.5 }] });
+///
+/// assert_eq!(value["name"].as_str(), Some("Hero"));
+/// assert_eq!(value["slots"][1][1].as_u64(), Some(2));
+/// assert_eq!(value["slots"][2]["x"].as_f64(), Some(-1.5));
+///
+/// let x = 3;
+/// let value = json!{ "a": 32, "list": [1, 2, x] };
+/// assert_eq!(value, json!({ "a": 32, "list": [1, 2, 3] }));
+/// ```
+#[macro_export]
+macro_rules! json {
+    (null) => {
+        $crate::value::Value::Null
+    };
+    ([]) => {
+        $crate::value::Value::Array(::std::vec::Vec::new())
+    };
+    ([ $($tt:tt)+ ]) => {
+        $crate::value::Value::Array($crate::json_internal!(@array [] () $($tt)+))
+    };
+    ({}) => {
+        $crate::value::Value::Object(::std::collections::BTreeMap::new())
+    };
+    ({ $($tt:tt)+ }) => {
+        $crate::value::Value::Object({
+            let mut object = ::std::collections::BTreeMap::new();
+            $crate::json_internal!(@object object $($tt)+);
+            object
+        })
+    };
+    ($key:tt : $($rest:tt)*) => {
+        $crate::json!({ $key : $($rest)* })
+    };
+    ($other:expr) => {
+        $crate::value::to_value(&$other).expect("failed to convert the interpolated expression")
+    };
+}
+
+/// Munches the tokens of [json] one element at a time, elements end at top level commas
+#[macro_export]
+#[doc(hidden)]
+macro_rules! json_internal {
+    (@array [$($elems:expr,)*] ()) => {
+        ::std::vec![$($elems,)*]
+    };
+    (@array [$($elems:expr,)*] ($($elem:tt)+) , $($rest:tt)*) => {
+        $crate::json_internal!(@array [$($elems,)* $crate::json!($($elem)+),] () $($rest)*)
+    };
+    (@array [$($elems:expr,)*] ($($elem:tt)+)) => {
+        $crate::json_internal!(@array [$($elems,)* $crate::json!($($elem)+),] ())
+    };
+    (@array [$($elems:expr,)*] ($($elem:tt)*) $next:tt $($rest:tt)*) => {
+        $crate::json_internal!(@array [$($elems,)*] ($($elem)* $next) $($rest)*)
+    };
+
+    (@object $object:ident) => {};
+    (@object $object:ident $key:tt : $($rest:tt)*) => {
+        $crate::json_internal!(@value $object ($key) () $($rest)*)
+    };
+    (@value $object:ident ($key:tt) ($($value:tt)+) , $($rest:tt)*) => {
+        $object.insert(($key).to_string(), $crate::json!($($value)+));
+        $crate::json_internal!(@object $object $($rest)*)
+    };
+    (@value $object:ident ($key:tt) ($($value:tt)+)) => {
+        $object.insert(($key).to_string(), $crate::json!($($value)+));
+    };
+    (@value $object:ident ($key:tt) ($($value:tt)*) $next:tt $($rest:tt)*) => {
+        $crate::json_internal!(@value $object ($key) ($($value)* $next) $($rest)*)
+    };
+}
+
+// ##########################################
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, ser: &mut S, pos: &Position) -> SuccessResult {
+        match self {
+            Value::Null => ser.serialize_null(),
+            Value::Bool(bool) => ser.serialize_bool(bool),
+            Value::Number(Number::Unsigned(number)) => ser.serialize_u128(number),
+            Value::Number(Number::Signed(number)) => ser.serialize_i128(number),
+            Value::Number(Number::Float(number)) => ser.serialize_f64(number),
+            Value::String(str) => ser.serialize_str(str),
+            Value::Array(array) => array.serialize(ser, pos),
+            Value::Object(object) => {
+                ser.begin_struct("Value", object.len())?;
+                let mut iter = object.iter();
+                let closing = iter.next_back();
+                for (key, value) in iter {
+                    ser.serialize_field(key, value, &Position::Trailing)?;
+                }
+                if let Some((key, value)) = closing {
+                    ser.serialize_field(key, value, &Position::Closing)?;
+                }
+                ser.end_struct("Value")
+            }
+        }
+    }
+}
+
+/// Captures whatever is serialized into a [Value]
+struct ValueSerializer {
+    value: Value,
+}
+
+macro_rules! impl_serialize_number {
+    ($type: ident, $ser_func: ident, $variant: ident, $wide: ident) => {
+        fn $ser_func(&mut self, value: &$type) -> SuccessResult {
+            self.value = Value::Number(Number::$variant(*value as $wide));
+            Ok(())
+        }
+    };
+}
+
+impl Serializer for ValueSerializer {
+    fn begin_struct(&mut self, _name: &str, _fields: usize) -> SuccessResult {
+        self.value = Value::Object(BTreeMap::new());
+        Ok(())
+    }
+
+    fn end_struct(&mut self, _name: &str) -> SuccessResult {
+        Ok(())
+    }
+
+    fn begin_collection(&mut self, _name: &str, size: usize) -> SuccessResult {
+        self.value = Value::Array(Vec::with_capacity(size));
+        Ok(())
+    }
+
+    fn end_collection(&mut self, _name: &str) -> SuccessResult {
+        Ok(())
+    }
+
+    fn serialize_item<V: Serialize>(
+        &mut self,
+        _i: usize,
+        item: &V,
+        _pos: &Position,
+    ) -> SuccessResult {
+        let item = to_value(item)?;
+        match self.value.as_array_mut() {
+            Some(array) => array.push(item),
+            None => return Err(Error::custom("serialized an item outside of a collection")),
+        }
+        Ok(())
+    }
+
+    fn serialize_field<V: Serialize>(
+        &mut self,
+        identifier: &str,
+        value: &V,
+        _pos: &Position,
+    ) -> SuccessResult {
+        let value = to_value(value)?;
+        match self.value.as_object_mut() {
+            Some(object) => object.insert(identifier.to_string(), value),
+            None => return Err(Error::custom("serialized a field outside of a struct")),
+        };
+        Ok(())
+    }
+
+    fn serialize_value<V: Serialize>(&mut self, value: &V, pos: &Position) -> SuccessResult {
+        value.serialize(self, pos)
+    }
+
+    fn serialize_str(&mut self, value: &str) -> SuccessResult {
+        self.value = Value::String(value.to_string());
+        Ok(())
+    }
+
+    fn serialize_null(&mut self) -> SuccessResult {
+        self.value = Value::Null;
+        Ok(())
+    }
+
+    fn serialize_bool(&mut self, value: &bool) -> SuccessResult {
+        self.value = Value::Bool(*value);
+        Ok(())
+    }
+
+    impl_serialize_number!(i8, serialize_i8, Signed, i128);
+    impl_serialize_number!(i16, serialize_i16, Signed, i128);
+    impl_serialize_number!(i32, serialize_i32, Signed, i128);
+    impl_serialize_number!(i64, serialize_i64, Signed, i128);
+    impl_serialize_number!(i128, serialize_i128, Signed, i128);
+    impl_serialize_number!(isize, serialize_isize, Signed, i128);
+    impl_serialize_number!(u8, serialize_u8, Unsigned, u128);
+    impl_serialize_number!(u16, serialize_u16, Unsigned, u128);
+    impl_serialize_number!(u32, serialize_u32, Unsigned, u128);
+    impl_serialize_number!(u64, serialize_u64, Unsigned, u128);
+    impl_serialize_number!(u128, serialize_u128, Unsigned, u128);
+    impl_serialize_number!(usize, serialize_usize, Unsigned, u128);
+    impl_serialize_number!(f32, serialize_f32, Float, f64);
+    impl_serialize_number!(f64, serialize_f64, Float, f64);
+}
+
+// ##########################################
+
+macro_rules! impl_visit_number {
+    ($type: ident, $visit_fn: ident, $variant: ident, $wide: ident) => {
+        fn $visit_fn(self, v: $type) -> Result<Self::Value, Error> {
+            Ok(Value::Number(Number::$variant(v as $wide)))
+        }
+    };
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(des: D) -> Result<Self, Error> {
+        struct ValueVisitor {}
+        impl<'de> Visitor<'de> for ValueVisitor {
+            type Value = Value;
+
+            fn expected_a(self) -> String {
+                "value".to_string()
+            }
+
+            fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, Error> {
+                let mut object = BTreeMap::new();
+                while let Some(key) = map.next_key::<String>()? {
+                    let value = map.next_value()?;
+                    object.insert(key, value);
+                }
+                Ok(Value::Object(object))
+            }
+
+            fn visit_seq<S: SeqAccess<'de>>(self, mut seq: S) -> Result<Self::Value, Error> {
+                let mut array = vec![];
+                while let Some(item) = seq.next_value()? {
+                    array.push(item);
+                }
+                Ok(Value::Array(array))
+            }
+
+            fn visit_str(self, v: &str) -> Result<Self::Value, Error> {
+                Ok(Value::String(v.to_string()))
+            }
+
+            fn visit_null(self) -> Result<Self::Value, Error> {
+                Ok(Value::Null)
+            }
+
+            fn visit_bool(self, v: bool) -> Result<Self::Value, Error> {
+                Ok(Value::Bool(v))
+            }
+
+            impl_visit_number!(i8, visit_i8, Signed, i128);
+            impl_visit_number!(i16, visit_i16, Signed, i128);
+            impl_visit_number!(i32, visit_i32, Signed, i128);
+            impl_visit_number!(i64, visit_i64, Signed, i128);
+            impl_visit_number!(i128, visit_i128, Signed, i128);
+            impl_visit_number!(isize, visit_isize, Signed, i128);
+            impl_visit_number!(u8, visit_u8, Unsigned, u128);
+            impl_visit_number!(u16, visit_u16, Unsigned, u128);
+            impl_visit_number!(u32, visit_u32, Unsigned, u128);
+            impl_visit_number!(u64, visit_u64, Unsigned, u128);
+            impl_visit_number!(u128, visit_u128, Unsigned, u128);
+            impl_visit_number!(usize, visit_usize, Unsigned, u128);
+            impl_visit_number!(f32, visit_f32, Float, f64);
+            impl_visit_number!(f64, visit_f64, Float, f64);
+        }
+
+        des.deserialize_any(ValueVisitor {})
+    }
+}
+
+/// Booleans as written in strings, e.g. by [environment variables](crate::deserialize::env)
+pub(crate) fn parse_bool(str: &str) -> Option<bool> {
+    match str.trim().to_lowercase().as_str() {
+        "true" | "1" => Some(true),
+        "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+macro_rules! impl_deserialize_number {
+    ($type: ident, $deserialize_fn: ident, $visit_fn: ident) => {
+        fn $deserialize_fn<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+            let str = match self {
+                Value::Number(number) => number.to_string(),
+                Value::String(str) => str,
+                other => return Err(Error::invalid_type(other.kind(), stringify!($type))),
+            };
+            match str.parse::<$type>() {
+                Ok(number) => v.$visit_fn(number),
+                Err(_) => Err(Error::invalid_value(
+                    format!("`{}`", str),
+                    stringify!($type),
+                )),
+            }
+        }
+    };
+}
+
+impl<'de> Deserializer<'de> for Value {
+    fn deserialize_map<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Object(object) => v.visit_map(ValueMap {
+                iter: object.into_iter(),
+                value: None,
+            }),
+            other => Err(Error::invalid_type(other.kind(), "map")),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Array(array) => v.visit_seq(ValueSeq {
+                iter: array.into_iter(),
+            }),
+            other => Err(Error::invalid_type(other.kind(), "seq")),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        self.deserialize_map(v)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        match self {
+            Value::String(str) => v.visit_str(&str),
+            other => Err(Error::invalid_type(other.kind(), "str")),
+        }
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        match self {
+            Value::Null => v.visit_null(),
+            Value::Bool(bool) => v.visit_bool(bool),
+            Value::Number(Number::Unsigned(number)) => v.visit_u128(number),
+            Value::Number(Number::Signed(number)) => v.visit_i128(number),
+            Value::Number(Number::Float(number)) => v.visit_f64(number),
+            Value::String(str) => v.visit_str(&str),
+            array @ Value::Array(_) => array.deserialize_seq(v),
+            object @ Value::Object(_) => object.deserialize_map(v),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, v: V) -> Result<V::Value, Error> {
+        match self.as_bool() {
+            Some(bool) => v.visit_bool(bool),
+            None => Err(Error::invalid_type(self.kind(), "bool")),
+        }
+    }
+
+    impl_deserialize_number!(i8, deserialize_i8, visit_i8);
+    impl_deserialize_number!(i16, deserialize_i16, visit_i16);
+    impl_deserialize_number!(i32, deserialize_i32, visit_i32);
+    impl_deserialize_number!(i64, deserialize_i64, visit_i64);
+    impl_deserialize_number!(i128, deserialize_i128, visit_i128);
+    impl_deserialize_number!(isize, deserialize_isize, visit_isize);
+    impl_deserialize_number!(u8, deserialize_u8, visit_u8);
+    impl_deserialize_number!(u16, deserialize_u16, visit_u16);
+    impl_deserialize_number!(u32, deserialize_u32, visit_u32);
+    impl_deserialize_number!(u64, deserialize_u64, visit_u64);
+    impl_deserialize_number!(u128, deserialize_u128, visit_u128);
+    impl_deserialize_number!(usize, deserialize_usize, visit_usize);
+    impl_deserialize_number!(f32, deserialize_f32, visit_f32);
+    impl_deserialize_number!(f64, deserialize_f64, visit_f64);
+}
+
+struct ValueMap {
+    iter: btree_map::IntoIter<String, Value>,
+    value: Option<Value>,
+}
+
+impl<'de> MapAccess<'de> for ValueMap {
+    fn next_value<V: Deserialize<'de>>(&mut self) -> Result<V, Error> {
+        match self.value.take() {
+            Some(value) => V::deserialize(value),
+            None => Err(Error::custom("requested a map value before its key")),
+        }
+    }
+
+    fn next_key<K: Deserialize<'de>>(&mut self) -> Result<Option<K>, Error> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                K::deserialize(Value::String(key)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+struct ValueSeq {
+    iter: vec::IntoIter<Value>,
+}
+
+impl<'de> SeqAccess<'de> for ValueSeq {
+    fn next_value<V: Deserialize<'de>>(&mut self) -> Result<Option<V>, Error> {
+        self.iter.next().map(V::deserialize).transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::{deserialize::json::FromJson, serialize::json::IntoJson};
+
+    #[test]
+    fn parse_any_json() {
+        let input =
+            "{ \"name\": \"Hero\", \"alive\": true, \"pet\": null, \"stats\": [1, -2, 0.5] }";
+        let value = Value::from_json(input).unwrap();
+
+        assert_eq!(value["name"].as_str(), Some("Hero"));
+        assert_eq!(value["alive"].as_bool(), Some(true));
+        assert!(value["pet"].is_null());
+        assert_eq!(value["stats"][0], Value::Number(Number::Unsigned(1)));
+        assert_eq!(value["stats"][1].as_i64(), Some(-2));
+        assert_eq!(value["stats"][2].as_f64(), Some(0.5));
+        assert!(value["stats"][3].is_null());
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let input = "{ \"a\": [true, null, \"x\\ny\"], \"b\": { \"c\": 18446744073709551616 } }";
+        let value = Value::from_json(input).unwrap();
+
+        let json = value.to_json().unwrap();
+        let result = Value::from_json(&json).unwrap();
+
+        // numbers are written as strings, the accessors read them back either way
+        assert_eq!(result["a"], value["a"]);
+        assert_eq!(result["b"]["c"].as_f64(), Some(18446744073709551616.0));
+        assert_eq!(result["b"]["c"].as_u64(), None);
+    }
+
+    #[test]
+    fn to_and_from_value() {
+        let mut map = HashMap::new();
+        map.insert("slots".to_string(), vec![1u8, 2, 3]);
+
+        let value = to_value(&vec!["a".to_string(), "b".to_string()]).unwrap();
+        assert_eq!(value[1].as_str(), Some("b"));
+
+        let value = Value::from_json("{ \"slots\": [\"1\", 2, 3] }").unwrap();
+        let result: HashMap<String, Vec<u8>> = from_value(value).unwrap();
+        assert_eq!(result, map);
+
+        let err = from_value::<Vec<u8>>(Value::Bool(true)).unwrap_err();
+        assert!(matches!(err.kind(), Error::InvalidType { .. }));
+        // strings are not split into sequences, just like in JSON
+        let err = from_value::<Vec<String>>(Value::String("w,a".to_string())).unwrap_err();
+        assert!(matches!(err.kind(), Error::InvalidType { .. }));
+    }
+
+    #[test]
+    fn display_is_compact_json() {
+        let value = crate::json!({ "a": [1, -2, 0.5, null, true], "b\"": "x\ny" });
+        assert_eq!(
+            value.to_string(),
+            "{\"a\":[1,-2,0.5,null,true],\"b\\\"\":\"x\\ny\"}"
+        );
+    }
+
+    #[test]
+    fn json_macro_matches_parsed_json() {
+        let name = "Hero";
+        let items = vec!["sword".to_string(), "shield".to_string()];
+        let value = crate::json!({
+            "name": name,
+            "health": 10 * 3,
+            "alive": true,
+            "pet": null,
+            ("lev".to_string() + "el"): -2,
+            "items": items,
+            "nested": [[], {}, [1.5, { "a": "32" },],],
+        });
+
+        let parsed = Value::from_json(
+            "{ \"name\": \"Hero\", \"health\": 30, \"alive\": true, \"pet\": null, \"level\": -2,
+              \"items\": [\"sword\", \"shield\"], \"nested\": [[], {}, [1.5, { \"a\": \"32\" }]] }",
+        )
+        .unwrap();
+        assert_eq!(value, parsed);
+    }
+}
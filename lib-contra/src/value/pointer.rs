@@ -0,0 +1,274 @@
+//! [RFC 6901](https://www.rfc-editor.org/rfc/rfc6901) JSON Pointers into [Value]s
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use crate::error::Error;
+
+use super::Value;
+
+/// Addresses a single value inside a document, e.g. `/graphics/resolution/0`
+///
+/// Every reference token selects a key of an object or an index of an array.
+/// `~1` and `~0` escape `/` and `~` within a token, the empty pointer addresses the whole document.
+///
+/// # Example
+/// ```
+/// use lib_contra::{json, value::pointer::Pointer};
+///
+/// let mut config = json!({ "graphics": { "resolution": [1920, 1080] } });
+/// let pointer: Pointer = "/graphics/resolution/0".parse().unwrap();
+///
+/// assert_eq!(pointer.get(&config).and_then(|v| v.as_u64()), Some(1920));
+/// pointer.set(&mut config, json!(2560)).unwrap();
+/// assert_eq!(config.pointer("/graphics/resolution/0").and_then(|v| v.as_u64()), Some(2560));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Pointer {
+    tokens: Vec<String>,
+}
+
+impl Pointer {
+    /// The pointer addressing the whole document
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    /// Parses a pointer such as `/a/b~1c/0`
+    pub fn parse(pointer: &str) -> Result<Self, Error> {
+        if pointer.is_empty() {
+            return Ok(Self::root());
+        }
+        let invalid = || Error::invalid_value(format!("`{}`", pointer), "a JSON pointer");
+        let rest = pointer.strip_prefix('/').ok_or_else(invalid)?;
+        let tokens = rest
+            .split('/')
+            .map(|token| unescape(token).ok_or_else(invalid))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { tokens })
+    }
+
+    /// The unescaped reference tokens
+    pub fn tokens(&self) -> &[String] {
+        &self.tokens
+    }
+
+    /// Extends the pointer by one reference token
+    pub fn push(&mut self, token: impl Into<String>) {
+        self.tokens.push(token.into());
+    }
+
+    /// Removes the last reference token
+    pub fn pop(&mut self) -> Option<String> {
+        self.tokens.pop()
+    }
+
+    /// The pointer without its last reference token, *None* for the root pointer
+    pub fn parent(&self) -> Option<Pointer> {
+        self.tokens.split_last().map(|(_, parent)| Pointer {
+            tokens: parent.to_vec(),
+        })
+    }
+
+    /// The last reference token, *None* for the root pointer
+    pub fn last(&self) -> Option<&str> {
+        self.tokens.last().map(String::as_str)
+    }
+
+    /// Whether *self* addresses *other* or a value inside of it
+    pub fn starts_with(&self, other: &Pointer) -> bool {
+        self.tokens.starts_with(&other.tokens)
+    }
+
+    /// The value addressed by the pointer, if present
+    pub fn get<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+        self.tokens
+            .iter()
+            .try_fold(value, |value, token| match value {
+                Value::Object(object) => object.get(token),
+                Value::Array(array) => index(token).and_then(|i| array.get(i)),
+                _ => None,
+            })
+    }
+
+    pub fn get_mut<'v>(&self, value: &'v mut Value) -> Option<&'v mut Value> {
+        self.tokens
+            .iter()
+            .try_fold(value, |value, token| match value {
+                Value::Object(object) => object.get_mut(token),
+                Value::Array(array) => index(token).and_then(|i| array.get_mut(i)),
+                _ => None,
+            })
+    }
+
+    /// Sets the addressed value, returning the one it replaced
+    ///
+    /// The parent of the addressed value must exist. Missing keys are inserted into objects,
+    /// for arrays the index one past the end or `-` appends.
+    pub fn set(&self, value: &mut Value, new: Value) -> Result<Option<Value>, Error> {
+        let (parent, last) = match (self.parent(), self.last()) {
+            (Some(parent), Some(last)) => (parent, last),
+            _ => return Ok(Some(std::mem::replace(value, new))),
+        };
+        match parent.get_mut(value) {
+            Some(Value::Object(object)) => Ok(object.insert(last.to_string(), new)),
+            Some(Value::Array(array)) => match last {
+                "-" => {
+                    array.push(new);
+                    Ok(None)
+                }
+                token => match index(token) {
+                    Some(i) if i < array.len() => Ok(Some(std::mem::replace(&mut array[i], new))),
+                    Some(i) if i == array.len() => {
+                        array.push(new);
+                        Ok(None)
+                    }
+                    _ => Err(self.missing()),
+                },
+            },
+            _ => Err(self.missing()),
+        }
+    }
+
+    /// Removes the addressed value, returning it if it was present
+    ///
+    /// Removing the whole document leaves [Value::Null] behind.
+    pub fn remove(&self, value: &mut Value) -> Option<Value> {
+        let (parent, last) = match (self.parent(), self.last()) {
+            (Some(parent), Some(last)) => (parent, last),
+            _ => return Some(std::mem::take(value)),
+        };
+        match parent.get_mut(value)? {
+            Value::Object(object) => object.remove(last),
+            Value::Array(array) => match index(last) {
+                Some(i) if i < array.len() => Some(array.remove(i)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub(crate) fn missing(&self) -> Error {
+        Error::custom(format!("the parent of `{}` does not exist", self))
+    }
+}
+
+/// Parses an array index token, which must not have leading zeros
+pub(crate) fn index(token: &str) -> Option<usize> {
+    match token.as_bytes() {
+        [b'0'] => Some(0),
+        [b'1'..=b'9', rest @ ..] if rest.iter().all(u8::is_ascii_digit) => token.parse().ok(),
+        _ => None,
+    }
+}
+
+fn unescape(token: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(token.len());
+    let mut chars = token.chars();
+    while let Some(char) = chars.next() {
+        match char {
+            '~' => match chars.next() {
+                Some('0') => unescaped.push('~'),
+                Some('1') => unescaped.push('/'),
+                _ => return None,
+            },
+            char => unescaped.push(char),
+        }
+    }
+    Some(unescaped)
+}
+
+impl FromStr for Pointer {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl Display for Pointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in self.tokens.iter() {
+            write!(f, "/{}", token.replace('~', "~0").replace('/', "~1"))?;
+        }
+        Ok(())
+    }
+}
+
+impl Value {
+    /// The value addressed by the JSON *pointer*, if present and the pointer is valid
+    pub fn pointer(&self, pointer: &str) -> Option<&Value> {
+        Pointer::parse(pointer).ok()?.get(self)
+    }
+
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value> {
+        Pointer::parse(pointer).ok()?.get_mut(self)
+    }
+
+    /// Sets the value addressed by the JSON *pointer*, see [Pointer::set]
+    pub fn pointer_set(&mut self, pointer: &str, new: Value) -> Result<Option<Value>, Error> {
+        Pointer::parse(pointer)?.set(self, new)
+    }
+
+    /// Removes the value addressed by the JSON *pointer*, if present and the pointer is valid
+    pub fn pointer_remove(&mut self, pointer: &str) -> Option<Value> {
+        Pointer::parse(pointer).ok()?.remove(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::json;
+
+    #[test]
+    fn parse_and_display() {
+        let pointer = Pointer::parse("/a~1b/~0c/0").unwrap();
+        assert_eq!(pointer.tokens(), &["a/b", "~c", "0"]);
+        assert_eq!(pointer.to_string(), "/a~1b/~0c/0");
+
+        assert!(Pointer::parse("").unwrap().tokens().is_empty());
+        assert!(Pointer::parse("a/b").is_err());
+        assert!(Pointer::parse("/a~2").is_err());
+    }
+
+    #[test]
+    fn get_follows_objects_and_arrays() {
+        let value = json!({ "a": [{ "b": 1 }, 2], "": 3, "c/d": 4 });
+
+        assert_eq!(value.pointer("/a/0/b"), Some(&json!(1)));
+        assert_eq!(value.pointer("/"), Some(&json!(3)));
+        assert_eq!(value.pointer("/c~1d"), Some(&json!(4)));
+        assert_eq!(value.pointer(""), Some(&value));
+        assert_eq!(value.pointer("/a/01"), None);
+        assert_eq!(value.pointer("/a/2"), None);
+        assert_eq!(value.pointer("/a/-"), None);
+    }
+
+    #[test]
+    fn set_and_remove() {
+        let mut value = json!({ "list": [1, 2] });
+
+        assert_eq!(
+            value.pointer_set("/list/0", json!(0)).unwrap(),
+            Some(json!(1))
+        );
+        assert_eq!(value.pointer_set("/list/-", json!(3)).unwrap(), None);
+        assert_eq!(value.pointer_set("/list/3", json!(4)).unwrap(), None);
+        assert_eq!(value.pointer_set("/name", json!("x")).unwrap(), None);
+        assert!(value.pointer_set("/list/9", json!(9)).is_err());
+        assert!(value.pointer_set("/missing/a", json!(9)).is_err());
+        assert!(value.pointer_set("name", json!(9)).is_err());
+        assert_eq!(value, json!({ "list": [0, 2, 3, 4], "name": "x" }));
+
+        assert_eq!(value.pointer_remove("/list/1"), Some(json!(2)));
+        assert_eq!(value.pointer_remove("/name"), Some(json!("x")));
+        assert_eq!(value.pointer_remove("/name"), None);
+        assert_eq!(value.pointer_remove("list"), None);
+        assert_eq!(value, json!({ "list": [0, 3, 4] }));
+
+        assert_eq!(value.pointer_remove(""), Some(json!({ "list": [0, 3, 4] })));
+        assert!(value.is_null());
+    }
+}
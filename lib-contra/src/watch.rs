@@ -0,0 +1,327 @@
+//! Hot reloading of files while the program runs
+//!
+//! The file is polled, either explicitly through [Watched::poll] or by a background thread started with [Watched::spawn].
+//! Its modification time and size are checked first, the content is only reloaded if its hash changed as well.
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    mem,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex, MutexGuard, PoisonError,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    deserialize::DeserializeOwned,
+    error::Error,
+    persistent::{deserializer_factory, read_bytes_file},
+};
+
+/// What happened to a [Watched] file
+pub enum Event<T> {
+    /// The file changed and was loaded
+    Reloaded(Arc<T>),
+    /// The file changed but could not be loaded, the previous value is kept
+    Failed(Arc<Error>),
+}
+
+impl<T> Clone for Event<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Event::Reloaded(value) => Event::Reloaded(value.clone()),
+            Event::Failed(err) => Event::Failed(err.clone()),
+        }
+    }
+}
+
+type Callback<T> = Box<dyn FnMut(&Event<T>) + Send>;
+
+/// Metadata checked on every poll, *None* if the file could not be inspected
+type Stamp = Option<(Option<SystemTime>, u64)>;
+
+struct State<T> {
+    value: Arc<T>,
+    stamp: Stamp,
+    hash: u64,
+}
+
+/// Each lock is only held briefly, so that callbacks may use the [Watched] they are called by
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    callbacks: Mutex<Vec<Callback<T>>>,
+    /// Held for the whole of [Watched::poll], so that concurrent polls notify in order
+    polling: Mutex<()>,
+}
+
+/// The latest value loaded from a file, as by [Persistent::load](crate::persistent::Persistent::load)
+///
+/// Clones share the value and the subscriptions.
+///
+/// # Example
+/// ```no_run
+/// use std::time::Duration;
+/// use lib_contra::watch::{Event, Watched};
+///
+/// let tuning = Watched::<Vec<f32>>::new("tuning.json").unwrap();
+/// tuning.on_change(|event| {
+///     if let Event::Failed(err) = event {
+///         eprintln!("keeping the previous tuning: {}", err);
+///     }
+/// });
+/// let _watching = tuning.spawn(Duration::from_millis(500));
+///
+/// loop {
+///     let speeds = tuning.get();
+///     // ...
+/// }
+/// ```
+pub struct Watched<T> {
+    path: PathBuf,
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Clone for Watched<T> {
+    fn clone(&self) -> Self {
+        Self {
+            path: self.path.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: DeserializeOwned> Watched<T> {
+    /// Loads the file, failing if it cannot be loaded
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+        let stamp = stamp(&path);
+        let content = read_bytes_file(&path)?;
+        let value = deserializer_factory(&content, &path)?;
+        Ok(Self {
+            path,
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    value: Arc::new(value),
+                    stamp,
+                    hash: hash(&content),
+                }),
+                callbacks: Mutex::new(vec![]),
+                polling: Mutex::new(()),
+            }),
+        })
+    }
+
+    /// The latest value that was loaded successfully
+    pub fn get(&self) -> Arc<T> {
+        lock(&self.shared.state).value.clone()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Calls *callback* on every change of the file
+    pub fn on_change(&self, callback: impl FnMut(&Event<T>) + Send + 'static) {
+        lock(&self.shared.callbacks).push(Box::new(callback));
+    }
+
+    /// Sends every change of the file to the returned receiver
+    pub fn subscribe(&self) -> mpsc::Receiver<Event<T>>
+    where
+        T: Send + Sync + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        self.on_change(move |event| {
+            // a dropped receiver only unsubscribes
+            let _ = sender.send(event.clone());
+        });
+        receiver
+    }
+
+    /// Checks the file once, reloading it if it changed
+    ///
+    /// Returns whether a new value was loaded, or the error that prevented it.
+    /// Subscribers are notified in both cases, a file that keeps failing is only reported once.
+    pub fn poll(&self) -> Result<bool, Error> {
+        let _polling = lock(&self.shared.polling);
+        let stamp = stamp(&self.path);
+        let known = {
+            let mut state = lock(&self.shared.state);
+            if stamp == state.stamp {
+                return Ok(false);
+            }
+            state.stamp = stamp;
+            state.hash
+        };
+
+        let loaded = read_bytes_file(&self.path)
+            .map_err(Error::from)
+            .and_then(|content| {
+                let hash = hash(&content);
+                match hash == known {
+                    true => Ok(None),
+                    false => deserializer_factory::<T>(&content, &self.path)
+                        .map(|value| Some((value, hash))),
+                }
+            });
+        let (event, result) = match loaded {
+            Ok(None) => return Ok(false),
+            Ok(Some((value, hash))) => {
+                let value = Arc::new(value);
+                let mut state = lock(&self.shared.state);
+                state.value = value.clone();
+                state.hash = hash;
+                (Event::Reloaded(value), Ok(true))
+            }
+            Err(err) => (Event::Failed(Arc::new(err.clone())), Err(err)),
+        };
+        self.notify(&event);
+        result
+    }
+
+    /// Calls the callbacks without holding their lock, callbacks registered meanwhile are kept
+    fn notify(&self, event: &Event<T>) {
+        let mut callbacks = mem::take(&mut *lock(&self.shared.callbacks));
+        for callback in callbacks.iter_mut() {
+            callback(event);
+        }
+        let mut registered = lock(&self.shared.callbacks);
+        let added = mem::replace(&mut *registered, callbacks);
+        registered.extend(added);
+    }
+
+    /// Polls the file every *interval* on a background thread until the returned handle is dropped
+    pub fn spawn(&self, interval: Duration) -> WatchHandle
+    where
+        T: Send + Sync + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let watched = self.clone();
+        let stopped = stop.clone();
+        let thread = thread::spawn(move || {
+            while !stopped.load(Ordering::Relaxed) {
+                // failures are reported to the subscribers
+                let _ = watched.poll();
+                thread::park_timeout(interval);
+            }
+        });
+        WatchHandle {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Stops the background polling of [Watched::spawn] when dropped
+pub struct WatchHandle {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+fn stamp(path: &Path) -> Stamp {
+    fs::metadata(path)
+        .map(|meta| (meta.modified().ok(), meta.len()))
+        .ok()
+}
+
+fn hash(content: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Instant;
+
+    use super::*;
+
+    struct FileLifetime(PathBuf);
+
+    impl Drop for FileLifetime {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    #[test]
+    fn reloads_changes_and_keeps_value_on_failure() {
+        let file = FileLifetime(PathBuf::from("watch_reload.json"));
+        fs::write(&file.0, "\"5\"").unwrap();
+        let watched = Watched::<u8>::new(&file.0).unwrap();
+        let events = watched.subscribe();
+        assert_eq!(*watched.get(), 5);
+        assert!(!watched.poll().unwrap());
+
+        fs::write(&file.0, "\"17\"").unwrap();
+        assert!(watched.poll().unwrap());
+        assert_eq!(*watched.get(), 17);
+        assert!(matches!(events.try_recv(), Ok(Event::Reloaded(value)) if *value == 17));
+
+        fs::write(&file.0, "{ corrupted").unwrap();
+        let err = watched.poll().unwrap_err();
+        assert!(!matches!(err.kind(), Error::Custom(_)));
+        assert_eq!(*watched.get(), 17);
+        assert!(matches!(events.try_recv(), Ok(Event::Failed(_))));
+        // the failure is not reported again as long as the file does not change
+        assert!(!watched.poll().unwrap());
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn callbacks_can_use_the_watched_file() {
+        let file = FileLifetime(PathBuf::from("watch_callback.json"));
+        fs::write(&file.0, "\"1\"").unwrap();
+        let watched = Watched::<u8>::new(&file.0).unwrap();
+        let (sender, seen) = mpsc::channel();
+        let inner = watched.clone();
+        watched.on_change(move |_| {
+            let _ = sender.send(*inner.get());
+            inner.on_change(|_| {});
+        });
+
+        fs::write(&file.0, "\"42\"").unwrap();
+        assert!(watched.poll().unwrap());
+        assert_eq!(seen.try_recv(), Ok(42));
+        assert_eq!(lock(&watched.shared.callbacks).len(), 2);
+    }
+
+    #[test]
+    fn background_polling_notifies_subscribers() {
+        let file = FileLifetime(PathBuf::from("watch_spawn.json"));
+        fs::write(&file.0, "\"1\"").unwrap();
+        let watched = Watched::<u8>::new(&file.0).unwrap();
+        let events = watched.subscribe();
+        let handle = watched.spawn(Duration::from_millis(5));
+
+        fs::write(&file.0, "\"200\"").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while *watched.get() != 200 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+        drop(handle);
+        assert_eq!(*watched.get(), 200);
+        // the file may have been seen half written before
+        assert!(events
+            .try_iter()
+            .any(|event| matches!(event, Event::Reloaded(value) if *value == 200)));
+    }
+}
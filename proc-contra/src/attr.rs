@@ -0,0 +1,230 @@
+//! Parsing of the `#[contra(...)]` helper attributes and doc comments
+
+use quote::{quote, ToTokens};
+use syn::{Attribute, Lit, Meta, MetaList, NestedMeta};
+
+/// The options a field can be annotated with
+#[derive(Default)]
+pub(crate) struct FieldAttrs {
+    /// `#[contra(default)]`: a missing field is initialized with *Default::default()*
+    pub default: bool,
+    /// `#[contra(validate(...))]`: constraints checked after deserialization
+    pub validations: Vec<Validation>,
+}
+
+/// The options a struct or enum can be annotated with
+#[derive(Default)]
+pub(crate) struct ContainerAttrs {
+    /// `#[contra(version = 3)]`: the version written along the fields
+    pub version: Option<u32>,
+    /// `#[contra(migrate("v1_to_v2", "v2_to_v3"))]`: the migrations leading up to *version*, oldest first
+    pub migrations: Vec<syn::Path>,
+}
+
+/// A single constraint of `#[contra(validate(...))]`
+pub(crate) enum Validation {
+    Range {
+        min: Option<Bound>,
+        max: Option<Bound>,
+    },
+    Length {
+        min: Option<usize>,
+        max: Option<usize>,
+    },
+    Regex(String),
+    Custom(syn::Path),
+}
+
+/// A bound of `range(...)`, emitted as *lib_contra::validate::Bound*
+pub(crate) enum Bound {
+    Integer(i128),
+    Float(f64),
+}
+
+impl ToTokens for Bound {
+    fn to_tokens(&self, tokens: &mut proc_macro2::TokenStream) {
+        tokens.extend(match self {
+            Bound::Integer(bound) => quote!(contra::lib_contra::validate::Bound::Integer(#bound)),
+            Bound::Float(bound) => quote!(contra::lib_contra::validate::Bound::Float(#bound)),
+        })
+    }
+}
+
+pub(crate) fn field_attrs(attrs: &[Attribute]) -> syn::Result<FieldAttrs> {
+    let mut field = FieldAttrs::default();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("contra")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(syn::Error::new_spanned(meta, "expected `contra(...)`")),
+        };
+        for nested in list.nested.iter() {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("default") => {
+                    field.default = true
+                }
+                NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("validate") => {
+                    for nested in list.nested.iter() {
+                        field.validations.push(validation(nested)?);
+                    }
+                }
+                nested => {
+                    return Err(syn::Error::new_spanned(
+                        nested,
+                        "unknown contra attribute, expected `default` or `validate(...)`",
+                    ))
+                }
+            }
+        }
+    }
+    Ok(field)
+}
+
+pub(crate) fn container_attrs(attrs: &[Attribute]) -> syn::Result<ContainerAttrs> {
+    let mut container = ContainerAttrs::default();
+    let mut span = None;
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("contra")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(syn::Error::new_spanned(meta, "expected `contra(...)`")),
+        };
+        span = Some(list.clone());
+        for nested in list.nested.iter() {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(meta)) if meta.path.is_ident("version") => {
+                    match &meta.lit {
+                        Lit::Int(lit) if lit.base10_parse::<u32>()? > 0 => {
+                            container.version = Some(lit.base10_parse()?)
+                        }
+                        lit => {
+                            return Err(syn::Error::new_spanned(
+                                lit,
+                                "expected a version greater than 0",
+                            ))
+                        }
+                    }
+                }
+                NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("migrate") => {
+                    for nested in list.nested.iter() {
+                        match nested {
+                            NestedMeta::Lit(Lit::Str(lit)) => {
+                                container.migrations.push(lit.parse()?)
+                            }
+                            nested => {
+                                return Err(syn::Error::new_spanned(
+                                    nested,
+                                    "expected a function path",
+                                ))
+                            }
+                        }
+                    }
+                }
+                nested => {
+                    return Err(syn::Error::new_spanned(
+                        nested,
+                        "unknown contra attribute, expected `version = ..` or `migrate(...)`",
+                    ))
+                }
+            }
+        }
+    }
+    let supported = container.version.unwrap_or(1) as usize - 1;
+    if container.migrations.len() > supported {
+        return Err(syn::Error::new_spanned(
+            span,
+            format!(
+                "expected at most {} migrations for version {}",
+                supported,
+                container.version.unwrap_or(1)
+            ),
+        ));
+    }
+    Ok(container)
+}
+
+fn validation(nested: &NestedMeta) -> syn::Result<Validation> {
+    match nested {
+        NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("range") => {
+            let (min, max) = bounds(list, |lit| match lit {
+                Lit::Int(lit) => Ok(Bound::Integer(lit.base10_parse()?)),
+                Lit::Float(lit) => Ok(Bound::Float(lit.base10_parse()?)),
+                lit => Err(syn::Error::new_spanned(lit, "expected a number")),
+            })?;
+            Ok(Validation::Range { min, max })
+        }
+        NestedMeta::Meta(Meta::List(list)) if list.path.is_ident("length") => {
+            let (min, max) = bounds(list, |lit| match lit {
+                Lit::Int(lit) => lit.base10_parse(),
+                lit => Err(syn::Error::new_spanned(lit, "expected a length")),
+            })?;
+            Ok(Validation::Length { min, max })
+        }
+        NestedMeta::Meta(Meta::NameValue(meta)) if meta.path.is_ident("regex") => {
+            match &meta.lit {
+                Lit::Str(lit) => Ok(Validation::Regex(lit.value())),
+                lit => Err(syn::Error::new_spanned(lit, "expected a string pattern")),
+            }
+        }
+        NestedMeta::Meta(Meta::NameValue(meta)) if meta.path.is_ident("custom") => {
+            match &meta.lit {
+                Lit::Str(lit) => Ok(Validation::Custom(lit.parse()?)),
+                lit => Err(syn::Error::new_spanned(lit, "expected a function path")),
+            }
+        }
+        nested => Err(syn::Error::new_spanned(
+            nested,
+            "unknown validation, expected `range(...)`, `length(...)`, `regex = \"...\"` or `custom = \"...\"`",
+        )),
+    }
+}
+
+/// Parses the optional `min = ..` and `max = ..` of *list*, their literals with *parse*
+fn bounds<N>(
+    list: &MetaList,
+    parse: impl Fn(&Lit) -> syn::Result<N>,
+) -> syn::Result<(Option<N>, Option<N>)> {
+    let (mut min, mut max) = (None, None);
+    for nested in list.nested.iter() {
+        let meta = match nested {
+            NestedMeta::Meta(Meta::NameValue(meta)) => meta,
+            nested => {
+                return Err(syn::Error::new_spanned(
+                    nested,
+                    "expected `min = ..` or `max = ..`",
+                ))
+            }
+        };
+        let bound = parse(&meta.lit)?;
+        match meta
+            .path
+            .get_ident()
+            .map(|ident| ident.to_string())
+            .as_deref()
+        {
+            Some("min") => min = Some(bound),
+            Some("max") => max = Some(bound),
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &meta.path,
+                    "expected `min` or `max`",
+                ))
+            }
+        }
+    }
+    Ok((min, max))
+}
+
+/// The doc comment lines joined by line breaks, empty if undocumented
+pub(crate) fn doc(attrs: &[Attribute]) -> String {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("doc"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::NameValue(meta)) => match meta.lit {
+                Lit::Str(lit) => Some(lit.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
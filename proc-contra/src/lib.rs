@@ -2,9 +2,12 @@
 //!
 //! Provides the derive macros for the serialization and deserialization of any arbitrary object.
 
+mod attr;
+
+use attr::{ContainerAttrs, FieldAttrs, Validation};
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{DataEnum, DataStruct, DeriveInput};
+use quote::{format_ident, quote};
+use syn::{parse_quote, DataEnum, DataStruct, DeriveInput, GenericParam, Generics};
 
 /// Derives the *Serialize* trait implementation
 ///
@@ -45,12 +48,18 @@ use syn::{DataEnum, DataStruct, DeriveInput};
 /// }
 /// ```
 ///
-#[proc_macro_derive(Serialize)]
+#[proc_macro_derive(Serialize, attributes(contra))]
 pub fn impl_serialize(input: TokenStream) -> TokenStream {
     let ast = syn::parse_macro_input!(input as DeriveInput);
+    let container = match container_attrs(&ast) {
+        Ok(container) => container,
+        Err(err) => return err.to_compile_error().into(),
+    };
 
     match ast.data {
-        syn::Data::Struct(decl) => gen_struct_serialize(ast.ident, decl),
+        syn::Data::Struct(decl) => {
+            gen_struct_serialize(ast.ident, ast.generics, decl, container.version)
+        }
         syn::Data::Enum(decl) => gen_enum_serialize(ast.ident, decl),
         syn::Data::Union(_) => todo!(),
     }
@@ -61,7 +70,7 @@ pub fn impl_serialize(input: TokenStream) -> TokenStream {
 /// # Example
 /// ```ignore
 /// use proc_contra::Deserialize;
-/// use lib_contra::{deserialize::Deserialize, position::Position, deserialize::Deserializer, error::AnyError};
+/// use lib_contra::{deserialize::Deserialize, position::Position, deserialize::Deserializer, error::Error};
 /// #[derive(Deserialize)]
 /// struct Point {
 ///     x: f32,
@@ -72,7 +81,8 @@ pub fn impl_serialize(input: TokenStream) -> TokenStream {
 ///
 /// Expands into:
 /// ```
-/// use lib_contra::{deserialize::{MapAccess, Visitor, Deserialize}, position::Position, deserialize::Deserializer, error::AnyError};
+/// use std::marker::PhantomData;
+/// use lib_contra::{deserialize::{MapAccess, Visitor, Deserialize}, position::Position, deserialize::Deserializer, error::Error};
 ///
 /// struct Point {
 ///     x: f32,
@@ -80,23 +90,24 @@ pub fn impl_serialize(input: TokenStream) -> TokenStream {
 ///     z: f32
 /// }
 ///
-/// impl Deserialize for Point {
-///     fn deserialize<D: Deserializer>(de: D) -> Result<Self, AnyError> {
+/// impl<'de> Deserialize<'de> for Point {
+///     fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, Error> {
+///         const FIELDS: &[&str] = &["x", "y", "z"];
 ///         enum Field {
 ///             x, y, z
 ///         }
-///         impl Deserialize for Field {
-///             fn deserialize<D: Deserializer>(de: D) -> Result<Self, AnyError> {
+///         impl<'de> Deserialize<'de> for Field {
+///             fn deserialize<D: Deserializer<'de>>(de: D) -> Result<Self, Error> {
 ///                 struct FieldVisitor {}
-///                 impl Visitor for FieldVisitor {
+///                 impl<'de> Visitor<'de> for FieldVisitor {
 ///                     type Value = Field;
 ///                     fn expected_a(self) -> String { "Point field".to_string() }
-///                     fn visit_str(self, v: &str) -> Result<Self::Value, AnyError> {
+///                     fn visit_str(self, v: &str) -> Result<Self::Value, Error> {
 ///                         match v {
 ///                             "x" => Ok(Field::x),
 ///                             "y" => Ok(Field::y),
 ///                             "z" => Ok(Field::z),
-///                             val => Err(format!("unexpected Point field {}", val).into())
+///                             val => Err(Error::unknown_field(val, FIELDS))
 ///                         }
 ///                     }
 ///                 }
@@ -104,26 +115,32 @@ pub fn impl_serialize(input: TokenStream) -> TokenStream {
 ///             }
 ///         }
 ///
-///         struct PointVisitor {}
-///         impl Visitor for PointVisitor {
+///         struct PointVisitor<'de> {
+///             marker: PhantomData<(&'de (), Point)>,
+///         }
+///         impl<'de> Visitor<'de> for PointVisitor<'de> {
 ///             type Value = Point;
 ///             fn expected_a(self) -> String { "Point object".to_string() }
-///             fn visit_map<M: MapAccess>(self, mut map: M) -> Result<Self::Value, AnyError> {
-///                 let mut x = None;
-///                 let mut y = None;
-///                 let mut z = None;
+///             fn visit_map<M: MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, Error> {
+///                 let mut __x = None; let mut __x_seen = false;
+///                 let mut __y = None; let mut __y_seen = false;
+///                 let mut __z = None; let mut __z_seen = false;
 ///                 
 ///                 while let Some(key) = map.next_key()? {
 ///                     match key {
-///                         Field::x => { if x.is_some() { return Err("duplicate field x".into()); } x = Some(map.next_value()?) },
-///                         Field::y => { if y.is_some() { return Err("duplicate field y".into()); } y = Some(map.next_value()?) },
-///                         Field::z => { if z.is_some() { return Err("duplicate field z".into()); } z = Some(map.next_value()?) },
+///                         Field::x => { if __x_seen { map.report(Error::duplicate_field("x"))?; } __x_seen = true; __x = map.next_value_or_report()? },
+///                         Field::y => { if __y_seen { map.report(Error::duplicate_field("y"))?; } __y_seen = true; __y = map.next_value_or_report()? },
+///                         Field::z => { if __z_seen { map.report(Error::duplicate_field("z"))?; } __z_seen = true; __z = map.next_value_or_report()? },
 ///                     }
 ///                 }
 ///
-///                 let x = x.ok_or_else(|| "missing field x")?;
-///                 let y = y.ok_or_else(|| "missing field y")?;
-///                 let z = z.ok_or_else(|| "missing field z")?;
+///                 if !__x_seen { map.report(Error::missing_field("x"))?; }
+///                 if !__y_seen { map.report(Error::missing_field("y"))?; }
+///                 if !__z_seen { map.report(Error::missing_field("z"))?; }
+///
+///                 let x = __x.ok_or_else(|| Error::missing_field("x"))?;
+///                 let y = __y.ok_or_else(|| Error::missing_field("y"))?;
+///                 let z = __z.ok_or_else(|| Error::missing_field("z"))?;
 ///
 ///                 Ok(Point {
 ///                     x, y, z
@@ -131,40 +148,184 @@ pub fn impl_serialize(input: TokenStream) -> TokenStream {
 ///             }
 ///         }
 ///
-///         de.deserialize_struct(PointVisitor {})
+///         de.deserialize_struct(PointVisitor { marker: PhantomData })
 ///     }
 /// }
 /// ```
-#[proc_macro_derive(Deserialize)]
+///
+/// Generic and borrowing structs are supported, every lifetime of the struct is outlived by the input lifetime *'de*:
+/// ```ignore
+/// #[derive(Deserialize)]
+/// struct Entry<'a, T> {
+///     key: &'a str,
+///     value: T,
+/// }
+/// ```
+/// implements `Deserialize<'de> for Entry<'a, T> where 'de: 'a, T: Deserialize<'de>`.
+///
+/// Fields marked `#[contra(default)]` are initialized with *Default::default()* if they are missing.
+/// Fields with `#[contra(validate(...))]` constraints make the struct implement *Validate*,
+/// the constraints are checked as soon as the struct is built, see *lib_contra::validate*.
+/// Structs annotated `#[contra(version = 3, migrate("v1_to_v2", "v2_to_v3"))]` migrate documents tagged with an older version
+/// before their fields are deserialized, see *lib_contra::migrate*.
+#[proc_macro_derive(Deserialize, attributes(contra))]
 pub fn impl_deserialize(input: TokenStream) -> TokenStream {
     let ast = syn::parse_macro_input!(input as DeriveInput);
+    let container = match container_attrs(&ast) {
+        Ok(container) => container,
+        Err(err) => return err.to_compile_error().into(),
+    };
 
     match ast.data {
-        syn::Data::Struct(decl) => gen_struct_deserialize(ast.ident, decl),
+        syn::Data::Struct(decl) => gen_struct_deserialize(ast.ident, ast.generics, decl, container),
         syn::Data::Enum(decl) => gen_enum_deserialize(ast.ident, decl),
         syn::Data::Union(_) => todo!(),
     }
 }
 
-fn gen_struct_serialize(ident: syn::Ident, decl: DataStruct) -> TokenStream {
+/// Derives the *Schema* trait implementation
+///
+/// Structs become objects and enums strings restricted to their variant names.
+/// Doc comments are used as descriptions and fields marked `#[contra(default)]` are not required.
+///
+/// # Example
+/// ```ignore
+/// use proc_contra::Schema;
+///
+/// /// A point in space
+/// #[derive(Schema)]
+/// struct Point {
+///     x: f32,
+///     y: f32,
+///     /// Zero if omitted
+///     #[contra(default)]
+///     z: f32
+/// }
+/// ```
+///
+/// Expands into:
+/// ```
+/// use lib_contra::{schema::{self, Schema}, value::Value};
+///
+/// struct Point {
+///     x: f32,
+///     y: f32,
+///     z: f32
+/// }
+///
+/// impl Schema for Point {
+///     fn schema() -> Value {
+///         schema::describe(
+///             schema::object(
+///                 "Point",
+///                 vec![
+///                     ("x", schema::describe(<f32 as Schema>::schema(), "")),
+///                     ("y", schema::describe(<f32 as Schema>::schema(), "")),
+///                     ("z", schema::describe(<f32 as Schema>::schema(), "Zero if omitted")),
+///                 ],
+///                 &["x", "y"],
+///             ),
+///             "A point in space",
+///         )
+///     }
+/// }
+/// ```
+#[proc_macro_derive(Schema, attributes(contra))]
+pub fn impl_schema(input: TokenStream) -> TokenStream {
+    let ast = syn::parse_macro_input!(input as DeriveInput);
+    let doc = attr::doc(&ast.attrs);
+    let container = match container_attrs(&ast) {
+        Ok(container) => container,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    match ast.data {
+        syn::Data::Struct(decl) => {
+            gen_struct_schema(ast.ident, ast.generics, decl, doc, container.version)
+        }
+        syn::Data::Enum(decl) => gen_enum_schema(ast.ident, decl, doc),
+        syn::Data::Union(decl) => {
+            syn::Error::new_spanned(decl.union_token, "Schema cannot be derived for unions")
+                .to_compile_error()
+                .into()
+        }
+    }
+}
+
+/// The container attributes of *ast*, versions are only supported on structs
+fn container_attrs(ast: &DeriveInput) -> syn::Result<ContainerAttrs> {
+    let container = attr::container_attrs(&ast.attrs)?;
+    match (&ast.data, container.version) {
+        (syn::Data::Struct(_), _) | (_, None) => Ok(container),
+        _ => Err(syn::Error::new_spanned(
+            &ast.ident,
+            "`version` is only supported on structs",
+        )),
+    }
+}
+
+/// Adds *bound* to every type parameter
+fn bound_generics(generics: &Generics, bound: syn::TypeParamBound) -> Generics {
+    let mut generics = generics.clone();
+    for param in generics.params.iter_mut() {
+        if let GenericParam::Type(param) = param {
+            param.bounds.push(bound.clone());
+        }
+    }
+    generics
+}
+
+/// Prepends the input lifetime *'de*, which outlives every lifetime of the type,
+/// and adds a *Deserialize<'de>* bound to every type parameter
+fn deserialize_generics(generics: &Generics) -> Generics {
+    let mut generics = generics.clone();
+    let mut de: syn::LifetimeDef = parse_quote!('de);
+    for param in generics.params.iter_mut() {
+        match param {
+            GenericParam::Lifetime(param) => de.bounds.push(param.lifetime.clone()),
+            GenericParam::Type(param) => param.bounds.push(parse_quote!(
+                contra::lib_contra::deserialize::Deserialize<'de>
+            )),
+            GenericParam::Const(_) => {}
+        }
+    }
+    generics.params.insert(0, GenericParam::Lifetime(de));
+    generics
+}
+
+fn gen_struct_serialize(
+    ident: syn::Ident,
+    generics: Generics,
+    decl: DataStruct,
+    version: Option<u32>,
+) -> TokenStream {
     let c_ident = ident;
-    let n_fields = decl.fields.len();
-    let mut ser_fields = decl
-        .fields
-        .into_iter()
-        .map(|f| f.ident)
-        .filter(|f| f.is_some())
-        .map(|f| f.unwrap());
+    let ser_generics = bound_generics(
+        &generics,
+        parse_quote!(contra::lib_contra::serialize::Serialize),
+    );
+    let (impl_generics, _, where_clause) = ser_generics.split_for_impl();
+    let (_, ty_generics, _) = generics.split_for_impl();
+    let n_fields = decl.fields.len() + version.iter().len();
+    let version_field = version.map(|version| {
+        let pos = match decl.fields.is_empty() {
+            true => quote!(Closing),
+            false => quote!(Trailing),
+        };
+        quote!(ser.serialize_field(contra::lib_contra::migrate::VERSION_FIELD, &#version, &contra::lib_contra::position::Position::#pos)?; )
+    });
+    let mut ser_fields = decl.fields.into_iter().filter_map(|f| f.ident);
     let closing_field = ser_fields.next_back()
-        .map(|f| Some(quote!(ser.serialize_field(stringify!(#f), &self.#f, &contra::lib_contra::position::Position::Closing )?; ))).into_iter();
+        .map(|f| quote!(ser.serialize_field(stringify!(#f), &self.#f, &contra::lib_contra::position::Position::Closing )?; ));
     let trailing_fields = ser_fields
-        .map(|f| Some(quote!(ser.serialize_field(stringify!(#f), &self.#f, &contra::lib_contra::position::Position::Trailing)?; ))).into_iter();
-    let ser_fields = trailing_fields
-        .chain(closing_field.into_iter())
-        .filter(|f| f.is_some());
+        .map(|f| quote!(ser.serialize_field(stringify!(#f), &self.#f, &contra::lib_contra::position::Position::Trailing)?; ));
+    let ser_fields = version_field
+        .into_iter()
+        .chain(trailing_fields)
+        .chain(closing_field);
 
     quote!(
-        impl contra::lib_contra::serialize::Serialize for #c_ident {
+        impl #impl_generics contra::lib_contra::serialize::Serialize for #c_ident #ty_generics #where_clause {
             fn serialize<S: contra::lib_contra::serialize::Serializer>(&self, ser: &mut S, _pos: &contra::lib_contra::position::Position) -> contra::lib_contra::error::SuccessResult {
                 ser.begin_struct(stringify!(#c_ident), #n_fields)?;
 
@@ -201,25 +362,30 @@ fn gen_enum_deserialize(ident: syn::Ident, decl: DataEnum) -> TokenStream {
     let e_ident = ident;
     let variants = decl.variants.into_iter().map(|v| v.ident);
 
+    let variant_names = variants.clone();
     let parse_variants = variants
         .clone()
         .map(|v| quote! { stringify!(#v) => Ok(#e_ident::#v) });
 
     quote! {
-        impl contra::lib_contra::deserialize::Deserialize for #e_ident {
-            fn deserialize<D: contra::lib_contra::deserialize::Deserializer>(des: D) -> Result<Self, contra::lib_contra::error::AnyError> {
+        impl<'de> contra::lib_contra::deserialize::Deserialize<'de> for #e_ident {
+            fn deserialize<D: contra::lib_contra::deserialize::Deserializer<'de>>(des: D) -> Result<Self, contra::lib_contra::error::Error> {
+                const VARIANTS: &[&str] = &[#(stringify!(#variant_names),)*];
                 struct EnumVisitor {}
-                impl contra::lib_contra::deserialize::Visitor for EnumVisitor {
+                impl<'de> contra::lib_contra::deserialize::Visitor<'de> for EnumVisitor {
                     type Value = #e_ident;
 
                     fn expected_a(self) -> String {
                         concat!(stringify!(#e_ident), " variant").to_string()
                     }
 
-                    fn visit_str(self, v: &str) -> Result<Self::Value, contra::lib_contra::error::AnyError> {
+                    fn visit_str(self, v: &str) -> Result<Self::Value, contra::lib_contra::error::Error> {
                         match v {
                             #(#parse_variants,)*
-                            err => Err(format!("invalid {} variant \"{}\"", stringify!(#e_ident), err).into())
+                            err => Err(contra::lib_contra::error::Error::invalid_value(
+                                format!("`{}`", err),
+                                &format!("one of `{}`", VARIANTS.join("`, `")),
+                            ))
                         }
                     }
                 }
@@ -230,53 +396,168 @@ fn gen_enum_deserialize(ident: syn::Ident, decl: DataEnum) -> TokenStream {
     }.into()
 }
 
-fn gen_struct_deserialize(ident: syn::Ident, decl: DataStruct) -> TokenStream {
+fn gen_struct_deserialize(
+    ident: syn::Ident,
+    generics: Generics,
+    decl: DataStruct,
+    container: ContainerAttrs,
+) -> TokenStream {
     let c_ident = ident;
-    let f_idents = decl
+    let de_generics = deserialize_generics(&generics);
+    let (impl_generics, visitor_generics, where_clause) = de_generics.split_for_impl();
+    let (_, ty_generics, _) = generics.split_for_impl();
+    let attrs = match decl
         .fields
-        .into_iter()
-        .map(|f| f.ident)
-        .filter(|f| f.is_some())
-        .map(|f| f.unwrap());
+        .iter()
+        .map(|f| attr::field_attrs(&f.attrs))
+        .collect::<syn::Result<Vec<_>>>()
+    {
+        Ok(attrs) => attrs,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let f_idents = decl.fields.into_iter().filter_map(|f| f.ident);
+    let validated: Vec<_> = f_idents
+        .clone()
+        .zip(attrs.iter())
+        .filter(|(_, attrs)| !attrs.validations.is_empty())
+        .collect();
+    let (validate_impl, validate_call) = match validated.is_empty() {
+        true => (quote!(), quote!()),
+        false => (
+            gen_validate(&c_ident, &generics, &validated),
+            quote! {
+                contra::lib_contra::validate::Validate::validate_each(&__value, &mut |err| map.report(err))?;
+            },
+        ),
+    };
 
-    let field_enum_decl = f_idents.clone().map(|i| quote! { #i });
-    let field_enum_parse = f_idents
+    let field_names = f_idents.clone();
+    let mut field_enum_decl: Vec<_> = f_idents.clone().map(|i| quote! { #i }).collect();
+    let mut field_enum_parse: Vec<_> = f_idents
         .clone()
-        .map(|i| quote! { stringify!(#i) => Ok(Field::#i) });
-    let tmp_field_decl = f_idents.clone().map(|i| quote! { let mut #i = None });
-    let tmp_field_parse = f_idents.clone().map(|i| {
+        .map(|i| quote! { stringify!(#i) => Ok(Field::#i) })
+        .collect();
+    // temporaries are prefixed so that fields named like the locals of the visitor, e.g. `key`, do not shadow them
+    let tmp_field_decl = f_idents.clone().map(|i| {
+        let tmp = format_ident!("__{}", i);
+        let seen = format_ident!("__{}_seen", i);
+        quote! { let mut #tmp = None; let mut #seen = false }
+    });
+    let mut tmp_field_parse: Vec<_> = f_idents.clone().map(|i| {
+        let tmp = format_ident!("__{}", i);
+        let seen = format_ident!("__{}_seen", i);
         quote! {
             Field::#i => {
-                if #i.is_some() {
-                    return Err(concat!("duplicate field ", stringify!(#i)).into());
+                if #seen {
+                    map.report(contra::lib_contra::error::Error::duplicate_field(stringify!(#i)))?;
                 }
-                #i = Some(map.next_value()?)
+                #seen = true;
+                #tmp = map.next_value_or_report()?
             }
         }
-    });
-    let tmp_field_result = f_idents
+    }).collect();
+    let tmp_field_missing = f_idents
         .clone()
-        .map(|i| quote! { let #i = #i.ok_or_else(|| concat!("missing field ", stringify!(#i)))? });
+        .zip(attrs.iter())
+        .filter(|(_, attrs)| !attrs.default)
+        .map(|(i, _)| {
+            let seen = format_ident!("__{}_seen", i);
+            quote! {
+                if !#seen {
+                    map.report(contra::lib_contra::error::Error::missing_field(stringify!(#i)))?;
+                }
+            }
+        });
+    let tmp_field_result = f_idents.clone().zip(attrs.iter()).map(|(i, attrs)| {
+        let tmp = format_ident!("__{}", i);
+        if attrs.default {
+            quote! { let #i = #tmp.unwrap_or_default() }
+        } else {
+            quote! { let #i = #tmp.ok_or_else(|| contra::lib_contra::error::Error::missing_field(stringify!(#i)))? }
+        }
+    });
     let tmp_field_initializer_list = f_idents.clone().map(|i| quote! { #i });
+    // formats that are not self-describing, e.g. binary, write the fields in declaration order without names
+    let n_fields = attrs.len() + container.version.is_some() as usize;
+    let seq_expected = format!("{} with {} fields", c_ident, n_fields);
+    let seq_version = container.version.map(|version| {
+        let expected_version = format!(
+            "version {}, documents that are not self-describing are not migrated",
+            version
+        );
+        quote! {
+            let __version: u32 = seq.next_value()?.ok_or_else(|| contra::lib_contra::error::Error::invalid_length(0, #seq_expected))?;
+            if __version != #version {
+                return Err(contra::lib_contra::error::Error::invalid_value(
+                    format!("version {}", __version),
+                    #expected_version,
+                ));
+            }
+        }
+    });
+    let seq_field_read = f_idents.clone().enumerate().map(|(n, i)| {
+        let tmp = format_ident!("__{}", i);
+        let n = n + container.version.is_some() as usize;
+        quote! {
+            let #tmp = seq.next_value()?.ok_or_else(|| contra::lib_contra::error::Error::invalid_length(#n, #seq_expected))?
+        }
+    });
+    let seq_field_initializer_list = f_idents.clone().map(|i| {
+        let tmp = format_ident!("__{}", i);
+        quote! { #i: #tmp }
+    });
+    let seq_validate_call = match validated.is_empty() {
+        true => quote!(),
+        false => quote! {
+            contra::lib_contra::validate::Validate::validate(&__value)?;
+        },
+    };
+    // documents tagged with an older version are migrated, the rest of their fields is captured once the tag is read
+    let (fields_decl, fields_count) = match container.version {
+        Some(version) => {
+            let migrations = container.migrations;
+            field_enum_decl.push(quote! { __version });
+            field_enum_parse.push(
+                quote! { v if v == contra::lib_contra::migrate::VERSION_FIELD => Ok(Field::__version) },
+            );
+            tmp_field_parse.push(quote! {
+                Field::__version => {
+                    let __found: u32 = map.next_value()?;
+                    if __found != #version {
+                        return contra::lib_contra::migrate::migrate_map(map, __found, __fields == 0, #version, &[#(#migrations,)*], StructVisitor {
+                            marker: std::marker::PhantomData,
+                        });
+                    }
+                }
+            });
+            (
+                quote! { let mut __fields = 0usize; },
+                quote! { __fields += 1; },
+            )
+        }
+        None => (quote!(), quote!()),
+    };
 
     quote!(
-        impl contra::lib_contra::deserialize::Deserialize for #c_ident {
-            fn deserialize<D: contra::lib_contra::deserialize::Deserializer>(de: D) -> Result<Self, contra::lib_contra::error::AnyError> {
+        impl #impl_generics contra::lib_contra::deserialize::Deserialize<'de> for #c_ident #ty_generics #where_clause {
+            fn deserialize<D: contra::lib_contra::deserialize::Deserializer<'de>>(de: D) -> Result<Self, contra::lib_contra::error::Error> {
+                const FIELDS: &[&str] = &[#(stringify!(#field_names),)*];
+                #[allow(non_camel_case_types)]
                 enum Field {
                     #(#field_enum_decl,)*
                 }
-                impl contra::lib_contra::deserialize::Deserialize for Field {
-                    fn deserialize<D: contra::lib_contra::deserialize::Deserializer>(de: D) -> Result<Self, contra::lib_contra::error::AnyError> {
+                impl<'de> contra::lib_contra::deserialize::Deserialize<'de> for Field {
+                    fn deserialize<D: contra::lib_contra::deserialize::Deserializer<'de>>(de: D) -> Result<Self, contra::lib_contra::error::Error> {
                         struct FieldVisitor {}
-                        impl contra::lib_contra::deserialize::Visitor for FieldVisitor {
+                        impl<'de> contra::lib_contra::deserialize::Visitor<'de> for FieldVisitor {
                             type Value = Field;
                             fn expected_a(self) -> String {
                                 concat!(stringify!(#c_ident), " field").into()
                             }
-                            fn visit_str(self, v: &str) -> Result<Self::Value, contra::lib_contra::error::AnyError> {
+                            fn visit_str(self, v: &str) -> Result<Self::Value, contra::lib_contra::error::Error> {
                                 match v {
                                     #(#field_enum_parse,)*
-                                    val => Err(format!("unknown \"{}\" field for {}", val, stringify!(#c_ident)).into())
+                                    val => Err(contra::lib_contra::error::Error::unknown_field(val, FIELDS))
                                 }
                             }
                         }
@@ -284,31 +565,177 @@ fn gen_struct_deserialize(ident: syn::Ident, decl: DataStruct) -> TokenStream {
                     }
                 }
 
-                struct StructVisitor {}
-                impl contra::lib_contra::deserialize::Visitor for StructVisitor {
-                    type Value = #c_ident;
+                struct StructVisitor #impl_generics #where_clause {
+                    marker: std::marker::PhantomData<(&'de (), #c_ident #ty_generics)>,
+                }
+                impl #impl_generics contra::lib_contra::deserialize::Visitor<'de> for StructVisitor #visitor_generics #where_clause {
+                    type Value = #c_ident #ty_generics;
                     fn expected_a(self) -> String {
                         concat!(stringify!(#c_ident), " object").into()
                     }
-                    fn visit_map<M: contra::lib_contra::deserialize::MapAccess>(self, mut map: M) -> Result<Self::Value, contra::lib_contra::error::AnyError> {
+                    fn visit_map<M: contra::lib_contra::deserialize::MapAccess<'de>>(self, mut map: M) -> Result<Self::Value, contra::lib_contra::error::Error> {
                         #(#tmp_field_decl;)*
+                        #fields_decl
 
                         while let Some(key) = map.next_key::<Field>()? {
                             match key {
                                 #(#tmp_field_parse,)*
                             }
+                            #fields_count
                         }
 
+                        #(#tmp_field_missing)*
                         #(#tmp_field_result;)*
 
-                        Ok(#c_ident {
+                        let __value = #c_ident {
                             #(#tmp_field_initializer_list,)*
-                        })
+                        };
+                        #validate_call
+                        Ok(__value)
+                    }
+                    fn visit_seq<S: contra::lib_contra::deserialize::SeqAccess<'de>>(self, mut seq: S) -> Result<Self::Value, contra::lib_contra::error::Error> {
+                        #seq_version
+                        #(#seq_field_read;)*
+
+                        let __value = #c_ident {
+                            #(#seq_field_initializer_list,)*
+                        };
+                        #seq_validate_call
+                        Ok(__value)
                     }
                 }
 
-                de.deserialize_struct(StructVisitor {})
+                contra::lib_contra::deserialize::Deserializer::deserialize_struct(de, StructVisitor {
+                    marker: std::marker::PhantomData,
+                })
             }
         }
+
+        #validate_impl
     ).into()
 }
+
+/// Implements *Validate* by checking the `#[contra(validate(...))]` constraints of the given fields
+fn gen_validate(
+    ident: &syn::Ident,
+    generics: &Generics,
+    fields: &[(syn::Ident, &FieldAttrs)],
+) -> proc_macro2::TokenStream {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let checks = fields.iter().flat_map(|(f, attrs)| {
+        attrs.validations.iter().map(move |validation| {
+            let check = match validation {
+                Validation::Range { min, max } => {
+                    let (min, max) = (option(min), option(max));
+                    quote!(contra::lib_contra::validate::range(&self.#f, #min, #max))
+                }
+                Validation::Length { min, max } => {
+                    let (min, max) = (option(min), option(max));
+                    quote!(contra::lib_contra::validate::length(&self.#f, #min, #max))
+                }
+                // compiled once per field
+                Validation::Regex(pattern) => quote!({
+                    static PATTERN: contra::lib_contra::validate::Pattern =
+                        contra::lib_contra::validate::Pattern::new(#pattern);
+                    PATTERN.check(&self.#f)
+                }),
+                Validation::Custom(path) => quote!(#path(&self.#f)),
+            };
+            quote! {
+                if let Err(reason) = #check {
+                    report(contra::lib_contra::error::Error::validation(stringify!(#f), reason))?;
+                }
+            }
+        })
+    });
+
+    quote!(
+        impl #impl_generics contra::lib_contra::validate::Validate for #ident #ty_generics #where_clause {
+            fn validate_each(&self, report: &mut dyn FnMut(contra::lib_contra::error::Error) -> contra::lib_contra::error::SuccessResult) -> contra::lib_contra::error::SuccessResult {
+                #(#checks)*
+                Ok(())
+            }
+        }
+    )
+}
+
+fn gen_struct_schema(
+    ident: syn::Ident,
+    generics: Generics,
+    decl: DataStruct,
+    doc: String,
+    version: Option<u32>,
+) -> TokenStream {
+    let c_ident = ident;
+    let sch_generics = bound_generics(&generics, parse_quote!(contra::lib_contra::schema::Schema));
+    let (impl_generics, _, where_clause) = sch_generics.split_for_impl();
+    let (_, ty_generics, _) = generics.split_for_impl();
+
+    let mut properties = vec![];
+    let mut required = vec![];
+    if version.is_some() {
+        properties.push(quote! {
+            (contra::lib_contra::migrate::VERSION_FIELD, contra::lib_contra::schema::describe(<u32 as contra::lib_contra::schema::Schema>::schema(), "Version of the document"))
+        });
+    }
+    for field in decl.fields.iter() {
+        let (f_ident, f_type) = match &field.ident {
+            Some(f_ident) => (f_ident, &field.ty),
+            None => continue,
+        };
+        let f_doc = attr::doc(&field.attrs);
+        match attr::field_attrs(&field.attrs) {
+            Ok(attrs) if attrs.default => {}
+            Ok(_) => required.push(f_ident),
+            Err(err) => return err.to_compile_error().into(),
+        }
+        properties.push(quote! {
+            (stringify!(#f_ident), contra::lib_contra::schema::describe(<#f_type as contra::lib_contra::schema::Schema>::schema(), #f_doc))
+        });
+    }
+
+    quote!(
+        impl #impl_generics contra::lib_contra::schema::Schema for #c_ident #ty_generics #where_clause {
+            fn schema() -> contra::lib_contra::value::Value {
+                contra::lib_contra::schema::describe(
+                    contra::lib_contra::schema::object(
+                        stringify!(#c_ident),
+                        vec![#(#properties,)*],
+                        &[#(stringify!(#required),)*],
+                    ),
+                    #doc,
+                )
+            }
+        }
+    )
+    .into()
+}
+
+fn gen_enum_schema(ident: syn::Ident, decl: DataEnum, doc: String) -> TokenStream {
+    let e_ident = ident;
+    let variants = decl.variants.into_iter().map(|v| {
+        let v_doc = attr::doc(&v.attrs);
+        let v_ident = v.ident;
+        quote! { (stringify!(#v_ident), #v_doc) }
+    });
+
+    quote!(
+        impl contra::lib_contra::schema::Schema for #e_ident {
+            fn schema() -> contra::lib_contra::value::Value {
+                contra::lib_contra::schema::describe(
+                    contra::lib_contra::schema::variants(stringify!(#e_ident), &[#(#variants,)*]),
+                    #doc,
+                )
+            }
+        }
+    )
+    .into()
+}
+
+/// The tokens of an *Option* literal
+fn option<T: quote::ToTokens>(value: &Option<T>) -> proc_macro2::TokenStream {
+    match value {
+        Some(value) => quote!(Some(#value)),
+        None => quote!(None),
+    }
+}
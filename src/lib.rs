@@ -26,16 +26,32 @@
 //! }
 //! ```
 
+pub use lib_contra::json;
 pub use lib_contra::{
     self,
-    deserialize::{self, json::FromJson, Deserialize},
+    deserialize::{self, args, json::FromJson, Deserialize, DeserializeOwned},
+    diagnostic,
+    diff::{self, diff, Change},
+    error::{self, Error},
+    layered::{self, Layered},
+    migrate,
+    patch::{self, Patch},
+    schema::{self, Schema},
     serialize::{self, json::IntoJson, Serialize},
+    validate::{self, Validate},
+    value::{self, from_value, to_value, Value},
+    watch,
 };
-pub use proc_contra::{Deserialize, Serialize};
+pub use proc_contra::{Deserialize, Schema, Serialize};
+
+/// Compact binary format, see [serialize::binary](lib_contra::serialize::binary)
+pub mod binary {
+    pub use lib_contra::{deserialize::binary::*, serialize::binary::*};
+}
 
 #[cfg(test)]
 mod test {
-    use super::{Deserialize, FromJson, IntoJson, Serialize};
+    use super::{json, schema, Deserialize, Error, FromJson, IntoJson, Schema, Serialize};
     use crate as contra;
 
     #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
@@ -164,4 +180,602 @@ mod test {
         assert!(result.is_ok());
         assert_eq!(a, result.unwrap());
     }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    struct Player {
+        name: String,
+        health: i32,
+    }
+
+    #[test]
+    fn missing_field_is_reported_as_kind() {
+        let result: Result<Player, Error> = FromJson::from_json("{ \"name\": \"Hero\" }");
+        let err = result.unwrap_err();
+        assert!(matches!(err.kind(), Error::MissingField("health")));
+    }
+
+    #[test]
+    fn unknown_field_is_reported_as_kind() {
+        let result: Result<Player, Error> =
+            FromJson::from_json("{ \"name\": \"Hero\", \"helth\": 10 }");
+        match result.as_ref().map_err(Error::kind) {
+            Err(Error::UnknownField { field, expected }) => {
+                assert_eq!(field, "helth");
+                assert_eq!(*expected, &["name", "health"]);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn diagnostic_renders_derived_struct_error() {
+        let source = "{\n\t\"name\": \"Hero\",\n\t\"helth\": \"10\"\n}";
+        let err = <Player as FromJson>::from_json(source).unwrap_err();
+
+        let rendered = contra::diagnostic::render(&err, source);
+        assert!(rendered.contains(" --> line 3, column 2"));
+        assert!(rendered.contains("3 | \t\"helth\": \"10\""));
+        assert!(rendered.contains("  | \t^^^^^^^"));
+        assert!(rendered.contains("did you mean `health`?"));
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Item {
+        name: String,
+        slot: u32,
+    }
+
+    #[derive(Serialize, Deserialize, Debug)]
+    struct Inventory {
+        owner: Player,
+        items: Vec<Item>,
+        gold: u32,
+    }
+
+    #[test]
+    fn collect_mode_reports_all_errors() {
+        let source = "{
+            \"owner\": { \"name\": \"Hero\", \"helth\": \"10\" },
+            \"items\": [
+                { \"name\": \"Sword\", \"slot\": \"x\" },
+                { \"name\": \"Shield\", \"slot\": \"2\", \"slot\": \"3\" }
+            ],
+            \"gold\": { \"amount\": 3 }
+        }";
+
+        let errors = <Inventory as FromJson>::from_json_collect(source).unwrap_err();
+        let errors: Vec<(String, &str)> = errors
+            .iter()
+            .map(|err| (err.kind().to_string(), err.path().unwrap_or_default()))
+            .collect();
+
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    "unknown field `helth`, expected one of `name`, `health`".to_string(),
+                    "owner"
+                ),
+                ("missing field `health`".to_string(), "owner"),
+                (
+                    "invalid value: found `x`, expected u32".to_string(),
+                    "items[0].slot"
+                ),
+                ("duplicate field `slot`".to_string(), "items[1].slot"),
+                ("invalid type: found map, expected u32".to_string(), "gold"),
+            ]
+        );
+    }
+
+    #[test]
+    fn collect_mode_succeeds_on_valid_input() {
+        let source = "{ \"name\": \"Hero\", \"health\": \"10\" }";
+        let player = <Player as FromJson>::from_json_collect(source).unwrap();
+        assert_eq!(
+            player,
+            Player {
+                name: "Hero".to_string(),
+                health: 10
+            }
+        );
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+    struct Localized<'a, T> {
+        key: &'a str,
+        text: std::borrow::Cow<'a, str>,
+        value: T,
+    }
+
+    #[test]
+    fn derive_borrows_from_input() {
+        let source = String::from(
+            "{ \"key\": \"menu.start\", \"text\": \"Start \\\"Game\\\"\", \"value\": \"3\" }",
+        );
+        let entry: Localized<u32> = FromJson::from_json(&source).unwrap();
+
+        assert_eq!(entry.key, "menu.start");
+        assert!(matches!(entry.text, std::borrow::Cow::Owned(_)));
+        assert_eq!(entry.text, "Start \"Game\"");
+        assert_eq!(entry.value, 3);
+
+        let json = IntoJson::to_json(&entry).unwrap();
+        let round_trip: Localized<u32> = FromJson::from_json(&json).unwrap();
+        assert_eq!(round_trip, entry);
+    }
+
+    /// Audio settings
+    #[derive(Serialize, Deserialize, Schema, Debug, PartialEq, Eq)]
+    struct Audio {
+        /// From 0 to 10
+        volume: u8,
+        #[contra(default)]
+        muted: bool,
+        quality: Quality,
+        devices: Vec<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Schema, Debug, PartialEq, Eq)]
+    enum Quality {
+        /// Mono, 22 kHz
+        Low,
+        High,
+    }
+
+    #[test]
+    fn default_fields_may_be_missing() {
+        let audio: Audio =
+            FromJson::from_json("{ \"volume\": 3, \"quality\": \"Low\", \"devices\": [] }")
+                .unwrap();
+        assert!(!audio.muted);
+
+        let result: Result<Audio, Error> = FromJson::from_json("{ \"muted\": true }");
+        assert!(matches!(
+            result.unwrap_err().kind(),
+            Error::MissingField("volume")
+        ));
+    }
+
+    #[test]
+    fn derived_schema_describes_fields_and_variants() {
+        let schema = schema::schema_for::<Audio>();
+
+        assert_eq!(schema["title"], json!("Audio"));
+        assert_eq!(schema["description"], json!("Audio settings"));
+        assert_eq!(schema["required"], json!(["volume", "quality", "devices"]));
+        assert_eq!(
+            schema["properties"]["volume"]["description"],
+            json!("From 0 to 10")
+        );
+        assert_eq!(schema["properties"]["muted"]["type"], json!("boolean"));
+        assert_eq!(
+            schema["properties"]["quality"]["enum"],
+            json!(["Low", "High"])
+        );
+        assert_eq!(
+            schema["properties"]["quality"]["enumDescriptions"],
+            json!(["Mono, 22 kHz", ""])
+        );
+        assert_eq!(
+            schema["properties"]["devices"]["items"],
+            json!({ "type": "string" })
+        );
+    }
+
+    fn no_spaces(name: &str) -> Result<(), &'static str> {
+        match name.contains(' ') {
+            true => Err("must not contain spaces"),
+            false => Ok(()),
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Character {
+        #[contra(validate(length(min = 1, max = 12), custom = "no_spaces"))]
+        name: String,
+        #[contra(validate(range(min = 1)))]
+        health: i32,
+        #[contra(validate(range(min = -1, max = 1.5)))]
+        volume: f32,
+    }
+
+    #[test]
+    fn validation_runs_after_deserialization() {
+        let character: Character =
+            FromJson::from_json("{ \"name\": \"Hero\", \"health\": 10, \"volume\": -0.5 }")
+                .unwrap();
+        assert_eq!(character.health, 10);
+
+        let result: Result<Character, Error> =
+            FromJson::from_json("{ \"name\": \"Hero\", \"health\": 0, \"volume\": 1 }");
+        match result.as_ref().map_err(Error::kind) {
+            Err(Error::Validation { path, reason }) => {
+                assert_eq!(path, "health");
+                assert_eq!(reason, "must be at least 1, found 0");
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn collect_mode_reports_all_violations() {
+        let source = "{ \"name\": \"Dark Lord\", \"health\": -5, \"volume\": 2 }";
+        let errors = <Character as FromJson>::from_json_collect(source).unwrap_err();
+        let paths: Vec<_> = errors
+            .iter()
+            .map(|err| match err.kind() {
+                Error::Validation { path, .. } => path.as_str(),
+                other => panic!("unexpected error {:?}", other),
+            })
+            .collect();
+        assert_eq!(paths, vec!["name", "health", "volume"]);
+    }
+
+    #[test]
+    fn validate_rechecks_edited_values() {
+        use super::validate::Validate;
+
+        let mut character = Character {
+            name: "Hero".to_string(),
+            health: 10,
+            volume: 1.0,
+        };
+        assert!(character.validate().is_ok());
+
+        character.name = String::new();
+        character.volume = 3.0;
+        let violations: Vec<String> = character
+            .violations()
+            .iter()
+            .map(Error::to_string)
+            .collect();
+        assert_eq!(
+            violations,
+            vec![
+                "invalid `name`: length must be at least 1, found 0",
+                "invalid `volume`: must be at most 1.5, found 3",
+            ]
+        );
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Ticket {
+        #[contra(validate(range(max = 18446744073709551614)))]
+        id: u64,
+    }
+
+    #[test]
+    fn integer_ranges_are_exact() {
+        use super::validate::Validate;
+
+        assert!(Ticket { id: u64::MAX - 1 }.validate().is_ok());
+        assert!(Ticket { id: u64::MAX }.validate().is_err());
+        let err = Ticket::from_json("{ \"id\": 18446744073709551615 }").unwrap_err();
+        assert!(matches!(err.kind(), Error::Validation { .. }));
+    }
+
+    #[cfg(feature = "regex")]
+    #[derive(Deserialize, Debug)]
+    struct Profile {
+        #[contra(validate(regex = "^[a-z_]+$"))]
+        id: String,
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn regex_validation() {
+        assert!(<Profile as FromJson>::from_json("{ \"id\": \"hero_one\" }").is_ok());
+        let err = <Profile as FromJson>::from_json("{ \"id\": \"Hero\" }").unwrap_err();
+        assert!(matches!(err.kind(), Error::Validation { .. }));
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Server {
+        port: u16,
+        graphics: Graphics,
+        admins: Vec<String>,
+        #[contra(default)]
+        debug: bool,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Graphics {
+        max_fps: u32,
+    }
+
+    #[test]
+    fn derived_struct_from_env() {
+        use super::deserialize::env::EnvDeserializer;
+
+        let vars = [
+            ("GAME_PORT", "8080"),
+            ("GAME_GRAPHICS__MAX_FPS", "60"),
+            ("GAME_ADMINS", "alice,bob"),
+        ];
+        let de = EnvDeserializer::from_vars(
+            "GAME_",
+            vars.iter().map(|(k, v)| (k.to_string(), v.to_string())),
+        );
+        let server: Server = de.deserialize_into().unwrap();
+        assert_eq!(
+            server,
+            Server {
+                port: 8080,
+                graphics: Graphics { max_fps: 60 },
+                admins: vec!["alice".to_string(), "bob".to_string()],
+                debug: false,
+            }
+        );
+    }
+
+    #[test]
+    fn derived_struct_from_args() {
+        let args = [
+            "game",
+            "--port",
+            "8080",
+            "--graphics.max_fps=60",
+            "--admins",
+            "alice",
+            "--admins",
+            "bob",
+            "--debug",
+        ];
+        let server: Server = super::args::from_args(args.map(String::from)).unwrap();
+        assert_eq!(
+            server,
+            Server {
+                port: 8080,
+                graphics: Graphics { max_fps: 60 },
+                admins: vec!["alice".to_string(), "bob".to_string()],
+                debug: true,
+            }
+        );
+
+        let err = super::args::from_args::<Server>(["game", "--help"].map(String::from));
+        let help = match err {
+            Err(Error::Help(help)) => help,
+            other => panic!("expected help, found {:?}", other),
+        };
+        assert_eq!(
+            help,
+            "Usage: game [OPTIONS]\n\nOptions:\n  --port <u16>\n  --graphics.max_fps <u32>\n  --admins <str>...\n  --debug\n  -h, --help  Print this help\n"
+        );
+    }
+
+    /// Version 1 called `health` `hp`, version 2 stored the resolution as `WIDTHxHEIGHT`
+    #[derive(Serialize, Deserialize, Schema, Debug, PartialEq)]
+    #[contra(version = 3, migrate("migrate_v1_to_v2", "migrate_v2_to_v3"))]
+    struct Settings {
+        name: String,
+        health: u32,
+        width: u32,
+        height: u32,
+    }
+
+    fn migrate_v1_to_v2(settings: &mut super::Value) -> super::error::SuccessResult {
+        if let Some(settings) = settings.as_object_mut() {
+            if let Some(hp) = settings.remove("hp") {
+                settings.insert("health".to_string(), hp);
+            }
+        }
+        Ok(())
+    }
+
+    fn migrate_v2_to_v3(settings: &mut super::Value) -> super::error::SuccessResult {
+        let resolution = settings
+            .get("resolution")
+            .and_then(super::Value::as_str)
+            .and_then(|resolution| resolution.split_once('x'))
+            .map(|(width, height)| (width.to_string(), height.to_string()))
+            .ok_or_else(|| Error::custom("expected a `WIDTHxHEIGHT` resolution"))?;
+        if let Some(settings) = settings.as_object_mut() {
+            settings.remove("resolution");
+            settings.insert("width".to_string(), super::Value::String(resolution.0));
+            settings.insert("height".to_string(), super::Value::String(resolution.1));
+        }
+        Ok(())
+    }
+
+    fn fixture(name: &str) -> std::path::PathBuf {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name)
+    }
+
+    #[test]
+    fn old_versions_are_migrated_on_load() {
+        use super::lib_contra::persistent::Persistent;
+
+        let current = Settings {
+            name: "hero".to_string(),
+            health: 100,
+            width: 1920,
+            height: 1080,
+        };
+        assert_eq!(
+            Settings::load(fixture("settings_v1.json")).unwrap(),
+            current
+        );
+        assert_eq!(
+            Settings::load(fixture("settings_v2.json")).unwrap(),
+            current
+        );
+
+        let json = current.to_json().unwrap();
+        assert!(json.starts_with("{\n\t\"_version\" : \"3\","));
+        assert_eq!(<Settings as FromJson>::from_json(&json).unwrap(), current);
+
+        let newer = json.replace("\"3\"", "\"4\"");
+        assert!(<Settings as FromJson>::from_json(&newer).is_err());
+
+        let schema = Settings::schema();
+        assert!(schema["properties"].get("_version").is_some());
+        assert!(!schema["required"]
+            .as_array()
+            .unwrap()
+            .contains(&json!("_version")));
+    }
+
+    #[test]
+    fn versioned_struct_from_every_source() {
+        use super::{args::from_args, deserialize::env::EnvDeserializer};
+
+        let current = Settings {
+            name: "hero".to_string(),
+            health: 100,
+            width: 1920,
+            height: 1080,
+        };
+        let vars = [
+            ("GAME_NAME", "hero"),
+            ("GAME_HEALTH", "100"),
+            ("GAME_WIDTH", "1920"),
+            ("GAME_HEIGHT", "1080"),
+        ];
+        let env = EnvDeserializer::from_vars(
+            "GAME_",
+            vars.iter().map(|(k, v)| (k.to_string(), v.to_string())),
+        );
+        assert_eq!(env.deserialize_into::<Settings>().unwrap(), current);
+
+        let args = [
+            "game", "--name", "hero", "--health", "100", "--width", "1920", "--height", "1080",
+        ];
+        assert_eq!(
+            from_args::<Settings>(args.map(String::from)).unwrap(),
+            current
+        );
+        let help = match from_args::<Settings>(["game", "--help"].map(String::from)) {
+            Err(Error::Help(help)) => help,
+            other => panic!("expected help, found {:?}", other),
+        };
+        assert!(help.contains("--health <u32>"));
+        assert!(!help.contains("_version"));
+
+        // errors keep their location, also in migrated documents
+        let json = current.to_json().unwrap().replace("\"100\"", "\"x\"");
+        let err = <Settings as FromJson>::from_json(&json).unwrap_err();
+        assert_eq!((err.line(), err.path()), (Some(4), Some("health")));
+        let old =
+            "{ \"_version\": 2, \"name\": \"hero\", \"health\": \"x\", \"resolution\": \"1x1\" }";
+        let err = <Settings as FromJson>::from_json(old).unwrap_err();
+        assert_eq!(err.line(), Some(1));
+
+        let errors = <Settings as FromJson>::from_json_collect(
+            "{ \"_version\": 3, \"name\": \"hero\", \"health\": \"x\", \"height\": 1 }",
+        )
+        .unwrap_err();
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn derived_struct_as_binary() {
+        use super::{binary, lib_contra::persistent::Persistent};
+
+        let settings = Settings {
+            name: "hero".to_string(),
+            health: 100,
+            width: 1920,
+            height: 1080,
+        };
+        let bytes = binary::to_bytes(&settings).unwrap();
+        // version, then the fields without names
+        assert_eq!(
+            bytes,
+            [3, 4, b'h', b'e', b'r', b'o', 100, 0x80, 0x0f, 0xb8, 0x08]
+        );
+        assert_eq!(binary::from_bytes::<Settings>(&bytes).unwrap(), settings);
+        assert!(binary::from_bytes::<Settings>(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut older = bytes.clone();
+        older[0] = 2;
+        assert!(binary::from_bytes::<Settings>(&older).is_err());
+
+        let path = std::env::temp_dir().join(format!("contra_settings_{}.bin", std::process::id()));
+        settings.save(&path).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), bytes);
+        assert_eq!(Settings::load(&path).unwrap(), settings);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn json_files_are_written_and_read_directly() {
+        use super::lib_contra::persistent::Persistent;
+
+        let inventory = Inventory {
+            owner: Player {
+                name: "hero".to_string(),
+                health: 10,
+            },
+            items: vec![
+                Item {
+                    name: "sword".to_string(),
+                    slot: 1,
+                },
+                Item {
+                    name: "shield".to_string(),
+                    slot: 2,
+                },
+            ],
+            gold: 50,
+        };
+        let path =
+            std::env::temp_dir().join(format!("contra_inventory_{}.json", std::process::id()));
+        inventory.save(&path).unwrap();
+        // fields keep their declaration order
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(saved, inventory.to_json().unwrap());
+        assert!(saved.find("owner").unwrap() < saved.find("gold").unwrap());
+
+        std::fs::write(&path, saved.replace("\"2\"", "\"x\"")).unwrap();
+        let err = Inventory::load(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(err, Error::Located { .. }));
+        assert_eq!(err.path(), Some("items[1].slot"));
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Cfg {
+        debug: bool,
+        muted: bool,
+        volume: u8,
+        keys: Vec<String>,
+        title: String,
+        mods: Vec<String>,
+    }
+
+    #[test]
+    fn env_overrides_layers_like_from_env() {
+        use super::{deserialize::env::EnvDeserializer, layered::Layered};
+
+        let vars = [
+            ("GAME_DEBUG", "1"),
+            ("GAME_MUTED", "TRUE"),
+            ("GAME_VOLUME", "1"),
+            ("GAME_KEYS", "w,a"),
+            ("GAME_TITLE", "Hello, world"),
+            ("GAME_MODS", "a"),
+        ];
+        let env = EnvDeserializer::from_vars(
+            "GAME_",
+            vars.iter().map(|(k, v)| (k.to_string(), v.to_string())),
+        );
+        let expected = Cfg {
+            debug: true,
+            muted: true,
+            volume: 1,
+            keys: vec!["w".to_string(), "a".to_string()],
+            title: "Hello, world".to_string(),
+            mods: vec!["a".to_string()],
+        };
+        let defaults = json!({
+            "debug": false, "muted": false, "volume": 5, "keys": [], "title": "", "mods": []
+        });
+        let layered = Layered::new()
+            .source(("defaults", defaults))
+            .source(env.clone());
+        assert_eq!(layered.load::<Cfg>().unwrap(), expected);
+        assert_eq!(env.deserialize_into::<Cfg>().unwrap(), expected);
+    }
 }